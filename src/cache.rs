@@ -0,0 +1,276 @@
+use crate::checksum::ChecksumReader;
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// The metadata is downloaded again after 48 hours, the same as dnf.
+pub const DEFAULT_METADATA_EXPIRE: Duration = Duration::from_secs(48 * 60 * 60);
+
+// $XDG_CACHE_HOME/rust-solv, or ~/.cache/rust-solv if it is not set.
+pub fn get_default_cachedir() -> PathBuf {
+    get_cachedir(
+        std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from),
+        std::env::var_os("HOME").map(PathBuf::from),
+    )
+}
+
+// Relative directories are ignored, so the cache does not depend on the working directory.
+// Without any home, e.g. in a system service, /var/cache/rust-solv is used.
+fn get_cachedir(cache_home: Option<PathBuf>, home: Option<PathBuf>) -> PathBuf {
+    match (
+        cache_home.filter(|dir| dir.is_absolute()),
+        home.filter(|dir| dir.is_absolute()),
+    ) {
+        (Some(cache_home), _) => cache_home.join("rust-solv"),
+        (None, Some(home)) => home.join(".cache/rust-solv"),
+        (None, None) => PathBuf::from("/var/cache/rust-solv"),
+    }
+}
+
+// Parse metadata_expire in the format of dnf, e.g. "3600", "90m", "6h", "2d",
+// and "never" or "-1" for metadata which never expires.
+pub fn parse_metadata_expire(s: &str) -> Result<Option<Duration>> {
+    let s = s.trim();
+    if s == "never" || s == "-1" {
+        return Ok(None);
+    }
+    let (number, unit) = match s.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&s[..index], unit),
+        _ => (s, 's'),
+    };
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid metadata_expire {:?}", s))?;
+    let seconds = match unit {
+        's' => number,
+        'm' => number * 60,
+        'h' => number * 60 * 60,
+        'd' => number * 60 * 60 * 24,
+        _ => return Err(anyhow!("Invalid metadata_expire {:?}", s)),
+    };
+    Ok(Some(Duration::from_secs(seconds)))
+}
+
+// The metadata of a repo cached on the disk, in <cachedir>/<repo id>/repodata.
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+    dir: PathBuf,
+    metadata_expire: Option<Duration>,
+    offline: bool,
+}
+
+impl MetadataCache {
+    pub fn new(cachedir: &Path, repo_id: &str) -> MetadataCache {
+        MetadataCache {
+            dir: cachedir.join(repo_id.replace('/', "_")),
+            metadata_expire: Some(DEFAULT_METADATA_EXPIRE),
+            offline: false,
+        }
+    }
+
+    // None means the cached metadata never expires.
+    pub fn set_metadata_expire(&mut self, metadata_expire: Option<Duration>) {
+        self.metadata_expire = metadata_expire;
+    }
+
+    // Only the cached metadata is used in offline mode, whether it has expired or not.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn get_repomd_path(&self) -> PathBuf {
+        self.dir.join("repodata").join("repomd.xml")
+    }
+
+    // The data files are stored by the file names in their location hrefs, which contain
+    // the checksums of the files in most repos.
+    fn get_data_path(&self, href: &str) -> PathBuf {
+        let file_name = href.rsplit('/').next().unwrap_or(href);
+        self.dir.join("repodata").join(file_name)
+    }
+
+    pub(crate) fn read_repomd(&self) -> Option<String> {
+        fs::read_to_string(self.get_repomd_path()).ok()
+    }
+
+    // The cached repomd.xml is fresh if it was checked against the repo within metadata_expire.
+    pub(crate) fn is_repomd_fresh(&self) -> bool {
+        let modified = match fs::metadata(self.get_repomd_path()).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+        match self.metadata_expire {
+            Some(metadata_expire) => SystemTime::now()
+                .duration_since(modified)
+                .map_or(true, |age| age <= metadata_expire),
+            None => true,
+        }
+    }
+
+    pub(crate) fn write_repomd(&self, repomd_xml: &str) -> Result<()> {
+        write_file(&self.get_repomd_path(), |file| {
+            file.write_all(repomd_xml.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    // Mark the cached repomd.xml as checked against the repo just now.
+    pub(crate) fn touch_repomd(&self) -> Result<()> {
+        let path = self.get_repomd_path();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .with_context(|| format!("Failed to update {:?}", path))
+    }
+
+    pub(crate) fn open_data(&self, href: &str) -> Option<BufReader<File>> {
+        File::open(self.get_data_path(href))
+            .ok()
+            .map(BufReader::new)
+    }
+
+    // Write the data while it is downloaded, and keep it only if its checksum matches.
+    pub(crate) fn write_data<R: BufRead>(
+        &self,
+        href: &str,
+        reader: &mut ChecksumReader<R>,
+    ) -> Result<()> {
+        write_file(&self.get_data_path(href), |file| {
+            io::copy(reader, file)?;
+            reader.verify()
+        })
+    }
+
+    // Remove the data files which are no longer listed in repomd.xml.
+    pub(crate) fn remove_stale_data(&self, hrefs: &[&str]) -> Result<()> {
+        let keep: Vec<PathBuf> = hrefs.iter().map(|href| self.get_data_path(href)).collect();
+        let entries = match fs::read_dir(self.dir.join("repodata")) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path != self.get_repomd_path() && !keep.contains(&path) {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Write to a temporary file first, so an interrupted write does not leave a broken cache.
+fn write_file(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut file =
+        File::create(&tmp_path).with_context(|| format!("Failed to write {:?}", tmp_path))?;
+    if let Err(error) = write(&mut file) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(error);
+    }
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to write {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::ChecksumType;
+    use std::io::Read;
+
+    #[test]
+    fn test_parse_metadata_expire() -> Result<()> {
+        assert_eq!(
+            parse_metadata_expire("3600")?,
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            parse_metadata_expire("90m")?,
+            Some(Duration::from_secs(5400))
+        );
+        assert_eq!(
+            parse_metadata_expire("6h")?,
+            Some(Duration::from_secs(21600))
+        );
+        assert_eq!(
+            parse_metadata_expire("2d")?,
+            Some(Duration::from_secs(172800))
+        );
+        assert_eq!(parse_metadata_expire("never")?, None);
+        assert_eq!(parse_metadata_expire("-1")?, None);
+        assert!(parse_metadata_expire("6w").is_err());
+        assert!(parse_metadata_expire("soon").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_cachedir() {
+        let dir = |dir: &str| Some(PathBuf::from(dir));
+        assert_eq!(
+            get_cachedir(dir("/tmp/cache"), dir("/root")),
+            Path::new("/tmp/cache/rust-solv")
+        );
+        assert_eq!(
+            get_cachedir(dir(""), dir("/root")),
+            Path::new("/root/.cache/rust-solv")
+        );
+        assert_eq!(get_cachedir(None, None), Path::new("/var/cache/rust-solv"));
+        assert_eq!(
+            get_cachedir(dir("cache"), dir("")),
+            Path::new("/var/cache/rust-solv")
+        );
+    }
+
+    #[test]
+    fn test_metadata_cache() -> Result<()> {
+        let cachedir = std::env::temp_dir().join(format!("rust-solv-cache-{}", std::process::id()));
+        let mut cache = MetadataCache::new(&cachedir, "OS");
+        assert!(cache.read_repomd().is_none());
+        assert!(!cache.is_repomd_fresh());
+        cache.write_repomd("<repomd/>")?;
+        for (href, data) in [
+            ("repodata/abc-primary.xml.gz", &b"primary"[..]),
+            ("repodata/abc-other.xml.gz", &b"other"[..]),
+        ] {
+            cache.write_data(href, &mut ChecksumReader::new(data, href, None))?;
+        }
+        // A corrupted file is not cached.
+        let href = "repodata/abc-filelists.xml.gz";
+        let expected = Some((ChecksumType::Sha256, "00".to_string()));
+        let mut reader = ChecksumReader::new(&b"filelists"[..], href, expected);
+        assert!(cache.write_data(href, &mut reader).is_err());
+        assert!(cache.open_data(href).is_none());
+        assert_eq!(cache.read_repomd().as_deref(), Some("<repomd/>"));
+        assert!(cache.is_repomd_fresh());
+        cache.set_metadata_expire(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!cache.is_repomd_fresh());
+        cache.touch_repomd()?;
+        cache.set_metadata_expire(None);
+        assert!(cache.is_repomd_fresh());
+
+        cache.remove_stale_data(&["repodata/abc-primary.xml.gz"])?;
+        let mut primary = Vec::new();
+        cache
+            .open_data("repodata/abc-primary.xml.gz")
+            .unwrap()
+            .read_to_end(&mut primary)?;
+        assert_eq!(primary, b"primary");
+        assert!(cache.open_data("repodata/abc-other.xml.gz").is_none());
+        assert!(cache.read_repomd().is_some());
+        fs::remove_dir_all(&cachedir)?;
+        Ok(())
+    }
+}
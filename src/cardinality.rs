@@ -0,0 +1,34 @@
+use varisat::{ExtendFormula, Lit};
+
+// Totalizer encoding of the number of true inputs (Bailleux and Boufkhad, 2003).
+// The k-th output (counting from 0) is forced to be true when at least k + 1 inputs are true,
+// so "at most k inputs are true" can be enforced by assuming the negation of the k-th output.
+// Only the first `limit` outputs are built, which is enough to enforce "at most limit - 1".
+pub fn build_totalizer(formula: &mut impl ExtendFormula, inputs: &[Lit], limit: usize) -> Vec<Lit> {
+    if inputs.len() <= 1 {
+        return inputs.iter().take(limit).copied().collect();
+    }
+    let (left, right) = inputs.split_at(inputs.len() / 2);
+    let left = build_totalizer(formula, left, limit);
+    let right = build_totalizer(formula, right, limit);
+    let outputs: Vec<Lit> = (0..(left.len() + right.len()).min(limit))
+        .map(|_| formula.new_lit())
+        .collect();
+    for i in 0..=left.len() {
+        for j in 0..=right.len() {
+            if i + j == 0 || i + j > outputs.len() {
+                continue;
+            }
+            let mut clause = Vec::with_capacity(3);
+            if i > 0 {
+                clause.push(!left[i - 1]);
+            }
+            if j > 0 {
+                clause.push(!right[j - 1]);
+            }
+            clause.push(outputs[i + j - 1]);
+            formula.add_clause(&clause);
+        }
+    }
+    outputs
+}
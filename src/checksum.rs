@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Error, Result};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumType {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl FromStr for ChecksumType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ChecksumType> {
+        // "sha" is the old name of sha1 used by createrepo.
+        match s {
+            "sha" | "sha1" => Ok(ChecksumType::Sha1),
+            "sha256" => Ok(ChecksumType::Sha256),
+            "sha512" => Ok(ChecksumType::Sha512),
+            _ => Err(anyhow!("Unsupported checksum type {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ChecksumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChecksumType::Sha1 => write!(f, "sha1"),
+            ChecksumType::Sha256 => write!(f, "sha256"),
+            ChecksumType::Sha512 => write!(f, "sha512"),
+        }
+    }
+}
+
+// The downloaded or decompressed data does not match the checksum in repomd.xml.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub location: String,
+    pub checksum_type: ChecksumType,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} checksum of {} does not match, expected {}, got {}",
+            self.checksum_type, self.location, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(checksum_type: ChecksumType) -> Hasher {
+        match checksum_type {
+            ChecksumType::Sha1 => Hasher::Sha1(Sha1::new()),
+            ChecksumType::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumType::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+fn check(
+    location: &str,
+    checksum_type: ChecksumType,
+    expected: &str,
+    actual: String,
+) -> Result<(), ChecksumMismatch> {
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch {
+            location: location.to_string(),
+            checksum_type,
+            expected: expected.trim().to_string(),
+            actual,
+        })
+    }
+}
+
+pub fn verify_checksum(
+    location: &str,
+    data: &[u8],
+    checksum_type: ChecksumType,
+    expected: &str,
+) -> Result<(), ChecksumMismatch> {
+    let mut hasher = Hasher::new(checksum_type);
+    hasher.update(data);
+    check(location, checksum_type, expected, hasher.finalize())
+}
+
+// Computes the checksum of the data while it is read, so large metadata can be verified
+// without keeping it in memory. Call verify after reading, even if the parsing failed,
+// to tell a corrupted file from a malformed one.
+pub struct ChecksumReader<R: BufRead> {
+    inner: R,
+    location: String,
+    expected: Option<(ChecksumType, String)>,
+    hasher: Option<Hasher>,
+}
+
+impl<R: BufRead> ChecksumReader<R> {
+    pub fn new(inner: R, location: &str, expected: Option<(ChecksumType, String)>) -> Self {
+        let hasher = expected
+            .as_ref()
+            .map(|(checksum_type, _)| Hasher::new(*checksum_type));
+        ChecksumReader {
+            inner,
+            location: location.to_string(),
+            expected,
+            hasher,
+        }
+    }
+
+    // The data read from the inner reader directly is not included in the checksum.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    // Read the rest of the data and compare its checksum with the expected one.
+    pub fn verify(&mut self) -> Result<()> {
+        io::copy(self, &mut io::sink())?;
+        if let (Some((checksum_type, expected)), Some(hasher)) =
+            (self.expected.as_ref(), self.hasher.take())
+        {
+            check(&self.location, *checksum_type, expected, hasher.finalize())?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..size]);
+        }
+        Ok(size)
+    }
+}
+
+impl<R: BufRead> BufRead for ChecksumReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(hasher) = self.hasher.as_mut() {
+            // The buffer is still filled, so this returns the same data without reading.
+            if let Ok(buf) = self.inner.fill_buf() {
+                hasher.update(&buf[..amt]);
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"<metadata packages=\"0\"></metadata>";
+
+    #[test]
+    fn test_verify_checksum() -> Result<()> {
+        let sha256 = format!("{:x}", Sha256::digest(DATA));
+        verify_checksum("primary.xml", DATA, "sha256".parse()?, &sha256)?;
+        verify_checksum(
+            "primary.xml",
+            DATA,
+            "sha".parse()?,
+            &format!("{:x}", Sha1::digest(DATA)),
+        )?;
+        let error = verify_checksum("primary.xml", DATA, ChecksumType::Sha512, "00").unwrap_err();
+        assert_eq!(error.expected, "00");
+        assert_eq!(error.actual, format!("{:x}", Sha512::digest(DATA)));
+        assert!("md5".parse::<ChecksumType>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_reader() -> Result<()> {
+        let sha256 = format!("{:x}", Sha256::digest(DATA));
+        // Both the read and the buffered read are hashed.
+        let mut reader =
+            ChecksumReader::new(DATA, "primary.xml", Some((ChecksumType::Sha256, sha256)));
+        let mut head = [0; 4];
+        reader.read_exact(&mut head)?;
+        let buffered = reader.fill_buf()?.len();
+        reader.consume(buffered / 2);
+        reader.verify()?;
+
+        let mut reader = ChecksumReader::new(
+            DATA,
+            "primary.xml",
+            Some((ChecksumType::Sha256, "00".to_string())),
+        );
+        let error = reader.verify().unwrap_err();
+        assert!(error.downcast_ref::<ChecksumMismatch>().is_some());
+
+        let mut reader = ChecksumReader::new(DATA, "primary.xml", None);
+        reader.verify()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use std::io::{self, BufRead, BufReader, Read};
+use xz2::read::XzDecoder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+    None,
+}
+
+impl Compression {
+    // Detect the compression by the extension of the location href in repomd.xml.
+    pub fn from_href(href: &str) -> Option<Compression> {
+        let extension = href.rsplit_once('.').map(|(_, extension)| extension)?;
+        match extension {
+            "gz" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            "bz2" => Some(Compression::Bzip2),
+            "xml" => Some(Compression::None),
+            _ => None,
+        }
+    }
+
+    // Detect the compression by the magic bytes at the beginning of the data.
+    pub fn from_magic(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if bytes.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+}
+
+// The decoder of the compressed data. The compressed reader is kept accessible, e.g. to read
+// the rest of it after the decompressed data.
+pub enum Decoder<R: BufRead> {
+    Gzip(BufReader<GzDecoder<R>>),
+    Zstd(BufReader<zstd::Decoder<'static, R>>),
+    Xz(BufReader<XzDecoder<R>>),
+    Bzip2(BufReader<BzDecoder<R>>),
+    None(R),
+}
+
+impl<R: BufRead> Decoder<R> {
+    pub fn get_mut(&mut self) -> &mut R {
+        match self {
+            Decoder::Gzip(reader) => reader.get_mut().get_mut(),
+            Decoder::Zstd(reader) => reader.get_mut().get_mut(),
+            Decoder::Xz(reader) => reader.get_mut().get_mut(),
+            Decoder::Bzip2(reader) => reader.get_mut().get_mut(),
+            Decoder::None(reader) => reader,
+        }
+    }
+
+    fn as_buf_read(&mut self) -> &mut dyn BufRead {
+        match self {
+            Decoder::Gzip(reader) => reader,
+            Decoder::Zstd(reader) => reader,
+            Decoder::Xz(reader) => reader,
+            Decoder::Bzip2(reader) => reader,
+            Decoder::None(reader) => reader,
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_buf_read().read(buf)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.as_buf_read().fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.as_buf_read().consume(amt)
+    }
+}
+
+// Wrap the reader with the decoder of the data. The compression is detected by the extension
+// of the href if it is known, otherwise by the magic bytes.
+pub fn get_decompressed_reader<R: BufRead>(href: &str, mut reader: R) -> Result<Decoder<R>> {
+    let compression = match Compression::from_href(href) {
+        Some(compression) => compression,
+        None => Compression::from_magic(reader.fill_buf()?),
+    };
+    Ok(match compression {
+        Compression::Gzip => Decoder::Gzip(BufReader::new(GzDecoder::new(reader))),
+        Compression::Zstd => Decoder::Zstd(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Decoder::Xz(BufReader::new(XzDecoder::new(reader))),
+        Compression::Bzip2 => Decoder::Bzip2(BufReader::new(BzDecoder::new(reader))),
+        Compression::None => Decoder::None(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn decompress(href: &str, data: &[u8]) -> Result<String> {
+        let mut xml = String::new();
+        get_decompressed_reader(href, data)?.read_to_string(&mut xml)?;
+        Ok(xml)
+    }
+
+    #[test]
+    fn test_decompress() -> Result<()> {
+        let xml = "<metadata packages=\"0\"></metadata>";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(xml.as_bytes())?;
+        let gz = gz.finish()?;
+        let zst = zstd::encode_all(xml.as_bytes(), 0)?;
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(xml.as_bytes())?;
+        let xz = xz.finish()?;
+        let mut bz2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz2.write_all(xml.as_bytes())?;
+        let bz2 = bz2.finish()?;
+        for (href, data) in [
+            ("repodata/primary.xml.gz", &gz),
+            ("repodata/primary.xml.zst", &zst),
+            ("repodata/primary.xml.xz", &xz),
+            ("repodata/primary.xml.bz2", &bz2),
+        ] {
+            assert_eq!(decompress(href, data)?, xml);
+            // Unknown extensions fall back to the magic bytes.
+            assert_eq!(decompress("repodata/primary", data)?, xml);
+        }
+        assert_eq!(decompress("repodata/primary.xml", xml.as_bytes())?, xml);
+        assert_eq!(decompress("repodata/primary", xml.as_bytes())?, xml);
+        Ok(())
+    }
+}
@@ -1,18 +1,121 @@
-use anyhow::{self, Context, Result};
+use crate::cache::{get_default_cachedir, parse_metadata_expire, MetadataCache};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use toml;
 
+// Packages which can be installed in several versions at the same time, the same as dnf.
+pub const DEFAULT_INSTALLONLYPKGS: [&str; 6] = [
+    "kernel",
+    "kernel-PAE",
+    "installonlypkg(kernel)",
+    "installonlypkg(kernel-module)",
+    "installonlypkg(vm)",
+    "multiversion(kernel)",
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
-    repoinfo: Repoinfo,
+    #[serde(default)]
+    main: Main,
+    repoinfo: Repoinfos,
+}
+
+// A single [repoinfo] table, or several [[repoinfo]] tables for the repos resolved together.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repoinfos {
+    One(Repoinfo),
+    Many(Vec<Repoinfo>),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Main {
+    installonlypkgs: Option<Vec<String>>,
+    cachedir: Option<String>,
+    metadata_expire: Option<MetadataExpire>,
+    offline: Option<bool>,
+    // Packages excluded from all the repos.
+    exclude: Option<Vec<String>>,
+    install_weak_deps: Option<bool>,
 }
 
+// metadata_expire can be given in seconds, or as a string like "6h" or "never".
 #[derive(Debug, Serialize, Deserialize)]
-struct Repoinfo {
+#[serde(untagged)]
+enum MetadataExpire {
+    Seconds(i64),
+    Text(String),
+}
+
+// A single baseurl, or several ones tried in order.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Baseurls {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Repoinfo {
     name: Option<String>,
-    baseurl: Option<String>,
+    enabled: Option<bool>,
+    baseurl: Option<Baseurls>,
+    mirrorlist: Option<String>,
+    metalink: Option<String>,
+    priority: Option<i32>,
+    cost: Option<i32>,
+    exclude: Option<Vec<String>>,
+    includepkgs: Option<Vec<String>>,
+}
+
+// The sections of an INI file like dnf.conf or a .repo file, in the order in the file.
+type IniSections = Vec<(String, HashMap<String, String>)>;
+
+fn load_ini(path: &Path) -> Result<IniSections> {
+    let mut ini = configparser::ini::Ini::new_cs();
+    // Options like baseurl can list their values on the indented lines after the key.
+    ini.set_multiline(true);
+    let map = ini
+        .load(path)
+        .map_err(|e| anyhow!("failed to parse {:?}: {}", path, e))?;
+    Ok(map
+        .into_iter()
+        .map(|(section, kvs)| {
+            let kvs = kvs
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| (key, value)))
+                .collect();
+            (section, kvs)
+        })
+        .collect())
+}
+
+// Boolean options of dnf, e.g. enabled=1 or enabled=False.
+fn parse_ini_bool(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "yes" | "true" | "on" => Ok(true),
+        "0" | "no" | "false" | "off" => Ok(false),
+        _ => Err(anyhow!("invalid boolean value {:?}", value)),
+    }
+}
+
+// List options of dnf are separated by commas or spaces.
+fn parse_ini_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+fn parse_ini_int(key: &str, value: &str) -> Result<i32> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid value {:?} of {}", value, key))
 }
 
 impl Config {
@@ -20,17 +123,333 @@ impl Config {
         toml::from_str(s).with_context(|| "failed to parse the config file.")
     }
 
+    // Load the repos in the .repo files of the directory, like /etc/yum.repos.d.
+    pub fn from_repos_dir(dir: &Path) -> Result<Config> {
+        Ok(Config {
+            main: Main::default(),
+            repoinfo: Repoinfos::Many(load_repos_dir(dir)?),
+        })
+    }
+
+    // Load the config of dnf in the installroot, i.e. <installroot>/etc/dnf/dnf.conf and
+    // the .repo files in its reposdir, which is <installroot>/etc/yum.repos.d by default.
+    pub fn from_dnf_conf(installroot: &Path) -> Result<Config> {
+        let mut main = Main::default();
+        let mut reposdirs = vec!["/etc/yum.repos.d".to_string()];
+        let dnf_conf = installroot.join("etc/dnf/dnf.conf");
+        if dnf_conf.exists() {
+            let sections = load_ini(&dnf_conf)?;
+            if let Some((_, kvs)) = sections.iter().find(|(section, _)| section == "main") {
+                // The cachedir of dnf is not used, as the cache of rust-solv is in another layout.
+                if let Some(value) = kvs.get("installonlypkgs") {
+                    main.installonlypkgs = Some(parse_ini_list(value));
+                }
+                if let Some(value) = kvs.get("metadata_expire") {
+                    main.metadata_expire = Some(MetadataExpire::Text(value.clone()));
+                }
+                if let Some(value) = kvs.get("exclude").or_else(|| kvs.get("excludepkgs")) {
+                    main.exclude = Some(parse_ini_list(value));
+                }
+                if let Some(value) = kvs.get("install_weak_deps") {
+                    main.install_weak_deps = Some(parse_ini_bool(value)?);
+                }
+                if let Some(value) = kvs.get("reposdir") {
+                    reposdirs = parse_ini_list(value);
+                }
+            }
+        }
+        let mut repoinfos = Vec::new();
+        for reposdir in reposdirs {
+            let dir = installroot.join(reposdir.trim_start_matches('/'));
+            if dir.is_dir() {
+                repoinfos.extend(load_repos_dir(&dir)?);
+            }
+        }
+        Ok(Config {
+            main,
+            repoinfo: Repoinfos::Many(repoinfos),
+        })
+    }
+
     pub fn from_file(path: &Path) -> Result<Config> {
         let s = fs::read_to_string(path)
             .with_context(|| format!("failed to open the config file {:?}.", path))?;
         Config::from_str(&s)
     }
 
+    pub fn get_repoinfos(&self) -> &[Repoinfo] {
+        match &self.repoinfo {
+            Repoinfos::One(repoinfo) => std::slice::from_ref(repoinfo),
+            Repoinfos::Many(repoinfos) => repoinfos,
+        }
+    }
+
+    // The name, baseurl and priority of the first repo.
     pub fn get_repo_name(&self) -> &Option<String> {
-        &self.repoinfo.name
+        match self.get_repoinfos().first() {
+            Some(repoinfo) => &repoinfo.name,
+            None => &None,
+        }
+    }
+
+    pub fn get_repo_baseurl(&self) -> Option<&str> {
+        self.get_repoinfos()
+            .first()
+            .and_then(|repoinfo| repoinfo.get_baseurl())
+    }
+
+    pub fn get_repo_priority(&self) -> Option<i32> {
+        self.get_repoinfos()
+            .first()
+            .and_then(|repoinfo| repoinfo.priority)
+    }
+
+    pub fn get_installonlypkgs(&self) -> Vec<String> {
+        match &self.main.installonlypkgs {
+            Some(pkgs) => pkgs.clone(),
+            None => DEFAULT_INSTALLONLYPKGS
+                .iter()
+                .map(|pkg| pkg.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn get_cachedir(&self) -> PathBuf {
+        match &self.main.cachedir {
+            Some(cachedir) => PathBuf::from(cachedir),
+            None => get_default_cachedir(),
+        }
+    }
+
+    // Packages excluded from the repo, by the [main] and the repo options.
+    pub fn get_excludes(&self, repoinfo: &Repoinfo) -> Vec<String> {
+        let mut excludes = self.main.exclude.clone().unwrap_or_default();
+        excludes.extend(repoinfo.exclude.iter().flatten().cloned());
+        excludes
+    }
+
+    // The weak dependencies are installed by default, the same as dnf.
+    pub fn get_install_weak_deps(&self) -> bool {
+        self.main.install_weak_deps.unwrap_or(true)
     }
 
-    pub fn get_repo_baseurl(&self) -> &Option<String> {
-        &self.repoinfo.baseurl
+    pub fn is_offline(&self) -> bool {
+        self.main.offline.unwrap_or(false)
+    }
+
+    // The cache of the repo, keyed by the id of the repo.
+    pub fn get_metadata_cache(&self, repoinfo: &Repoinfo) -> Result<MetadataCache> {
+        let mut cache = MetadataCache::new(&self.get_cachedir(), &repoinfo.get_id());
+        match &self.main.metadata_expire {
+            Some(MetadataExpire::Seconds(seconds)) => {
+                cache.set_metadata_expire(parse_metadata_expire(&seconds.to_string())?)
+            }
+            Some(MetadataExpire::Text(text)) => {
+                cache.set_metadata_expire(parse_metadata_expire(text)?)
+            }
+            None => (),
+        }
+        cache.set_offline(self.is_offline());
+        Ok(cache)
+    }
+}
+
+impl Repoinfo {
+    // A section of a .repo file, whose name is the id of the repo.
+    fn from_ini_section(id: &str, kvs: &HashMap<String, String>) -> Result<Repoinfo> {
+        let mut repoinfo = Repoinfo {
+            name: Some(id.to_string()),
+            ..Default::default()
+        };
+        if let Some(value) = kvs.get("enabled") {
+            repoinfo.enabled = Some(parse_ini_bool(value)?);
+        }
+        if let Some(value) = kvs.get("baseurl") {
+            repoinfo.baseurl = Some(Baseurls::Many(parse_ini_list(value)));
+        }
+        repoinfo.mirrorlist = kvs.get("mirrorlist").map(|value| value.trim().to_string());
+        repoinfo.metalink = kvs.get("metalink").map(|value| value.trim().to_string());
+        if let Some(value) = kvs.get("priority") {
+            repoinfo.priority = Some(parse_ini_int("priority", value)?);
+        }
+        if let Some(value) = kvs.get("cost") {
+            repoinfo.cost = Some(parse_ini_int("cost", value)?);
+        }
+        if let Some(value) = kvs.get("exclude").or_else(|| kvs.get("excludepkgs")) {
+            repoinfo.exclude = Some(parse_ini_list(value));
+        }
+        if let Some(value) = kvs.get("includepkgs") {
+            repoinfo.includepkgs = Some(parse_ini_list(value));
+        }
+        Ok(repoinfo)
+    }
+
+    pub fn get_name(&self) -> &Option<String> {
+        &self.name
+    }
+
+    // Repos are enabled unless enabled = false, the same as dnf.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn get_mirrorlist(&self) -> &Option<String> {
+        &self.mirrorlist
+    }
+
+    pub fn get_metalink(&self) -> &Option<String> {
+        &self.metalink
+    }
+
+    pub fn get_cost(&self) -> Option<i32> {
+        self.cost
+    }
+
+    // Only the packages matching includepkgs are used from the repo, if it is not empty.
+    pub fn get_includepkgs(&self) -> &[String] {
+        self.includepkgs.as_deref().unwrap_or_default()
+    }
+
+    // The first baseurl, which identifies the repo.
+    pub fn get_baseurl(&self) -> Option<&str> {
+        self.get_baseurls().first().map(String::as_str)
+    }
+
+    pub fn get_baseurls(&self) -> &[String] {
+        match &self.baseurl {
+            Some(Baseurls::One(baseurl)) => std::slice::from_ref(baseurl),
+            Some(Baseurls::Many(baseurls)) => baseurls,
+            None => &[],
+        }
+    }
+
+    pub fn get_priority(&self) -> Option<i32> {
+        self.priority
+    }
+
+    // The repo is identified by its name, or by its baseurl if it has no name.
+    pub fn get_id(&self) -> String {
+        match (&self.name, self.get_baseurl()) {
+            (Some(name), _) => name.clone(),
+            (None, Some(baseurl)) => baseurl.to_string(),
+            (None, None) => "default".to_string(),
+        }
+    }
+}
+
+// Load the repos in all the .repo files of the directory, sorted by the file names.
+fn load_repos_dir(dir: &Path) -> Result<Vec<Repoinfo>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("failed to open the repos directory {:?}.", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "repo")
+        })
+        .collect();
+    paths.sort();
+    let mut repoinfos = Vec::new();
+    for path in paths {
+        for (id, kvs) in load_ini(&path)? {
+            repoinfos.push(Repoinfo::from_ini_section(&id, &kvs)?);
+        }
+    }
+    Ok(repoinfos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repoinfos() -> Result<()> {
+        let cfg = Config::from_str(
+            r#"
+[repoinfo]
+name = "OS"
+baseurl = "http://repo.openeuler.org/openEuler-22.03-LTS/OS/$basearch/"
+"#,
+        )?;
+        assert_eq!(cfg.get_repoinfos().len(), 1);
+        assert_eq!(cfg.get_repo_name().as_deref(), Some("OS"));
+
+        let cfg = Config::from_str(
+            r#"
+[[repoinfo]]
+name = "OS"
+baseurl = "http://repo.openeuler.org/openEuler-22.03-LTS/OS/$basearch/"
+
+[[repoinfo]]
+name = "update"
+baseurl = "http://repo.openeuler.org/openEuler-22.03-LTS/update/$basearch/"
+priority = 10
+"#,
+        )?;
+        let repoinfos = cfg.get_repoinfos();
+        assert_eq!(repoinfos.len(), 2);
+        assert_eq!(repoinfos[1].get_id(), "update");
+        assert_eq!(repoinfos[1].get_priority(), Some(10));
+        assert_eq!(cfg.get_repo_priority(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dnf_conf() -> Result<()> {
+        let installroot =
+            std::env::temp_dir().join(format!("rust-solv-installroot-{}", std::process::id()));
+        fs::create_dir_all(installroot.join("etc/dnf"))?;
+        fs::create_dir_all(installroot.join("etc/yum.repos.d"))?;
+        fs::write(
+            installroot.join("etc/dnf/dnf.conf"),
+            "[main]\ninstallonlypkgs=kernel kernel-devel\nmetadata_expire=6h\nexclude=foo*\ninstall_weak_deps=False\n",
+        )?;
+        fs::write(
+            installroot.join("etc/yum.repos.d/openEuler.repo"),
+            r#"[OS]
+name=OS
+baseurl=http://repo.openeuler.org/openEuler-22.03-LTS/OS/$basearch/
+        http://mirror.example.com/OS/$basearch/
+enabled=1
+cost=500
+
+[everything]
+name=everything
+metalink=https://mirrors.openeuler.org/metalink?repo=everything&arch=$basearch
+enabled=0
+exclude=bar, baz*
+includepkgs=bar*
+"#,
+        )?;
+        fs::write(
+            installroot.join("etc/yum.repos.d/update.repo"),
+            "[update]\nbaseurl=file:///srv/update\npriority=10\n",
+        )?;
+        fs::write(installroot.join("etc/yum.repos.d/README"), "[ignored]\n")?;
+
+        let cfg = Config::from_dnf_conf(&installroot)?;
+        fs::remove_dir_all(&installroot)?;
+        assert_eq!(cfg.get_installonlypkgs(), vec!["kernel", "kernel-devel"]);
+        assert!(!cfg.get_install_weak_deps());
+        let repoinfos = cfg.get_repoinfos();
+        assert_eq!(repoinfos.len(), 3);
+        assert_eq!(repoinfos[0].get_id(), "OS");
+        assert!(repoinfos[0].is_enabled());
+        assert_eq!(
+            repoinfos[0].get_baseurls(),
+            [
+                "http://repo.openeuler.org/openEuler-22.03-LTS/OS/$basearch/",
+                "http://mirror.example.com/OS/$basearch/"
+            ]
+        );
+        assert_eq!(repoinfos[0].get_cost(), Some(500));
+        assert_eq!(repoinfos[1].get_id(), "everything");
+        assert!(!repoinfos[1].is_enabled());
+        assert!(repoinfos[1].get_metalink().is_some());
+        assert_eq!(repoinfos[1].get_includepkgs(), ["bar*"]);
+        assert_eq!(cfg.get_excludes(&repoinfos[1]), ["foo*", "bar", "baz*"]);
+        assert_eq!(repoinfos[2].get_id(), "update");
+        assert_eq!(repoinfos[2].get_priority(), Some(10));
+        assert_eq!(cfg.get_excludes(&repoinfos[2]), ["foo*"]);
+        Ok(())
     }
 }
@@ -0,0 +1,33 @@
+use crate::repo::Version;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileEntry {
+    // "dir" or "ghost", regular files have no type.
+    pub r#type: Option<String>,
+    #[serde(rename = "$value")]
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FilelistsPackage {
+    pub pkgid: String,
+    pub name: String,
+    pub arch: String,
+    pub version: Version,
+    #[serde(rename = "file", default)]
+    pub files: Vec<FileEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Filelists {
+    #[serde(rename = "package", default)]
+    pub packages: Vec<FilelistsPackage>,
+}
+
+impl Filelists {
+    pub fn from_str(filelists_xml: &str) -> Result<Filelists> {
+        quick_xml::de::from_str(filelists_xml).with_context(|| "Failed to parse filelists.xml")
+    }
+}
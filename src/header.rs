@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+// The tags of the rpm header used by rust-solv, see rpmtag.h of rpm.
+pub const RPMTAG_NAME: u32 = 1000;
+pub const RPMTAG_VERSION: u32 = 1001;
+pub const RPMTAG_RELEASE: u32 = 1002;
+pub const RPMTAG_EPOCH: u32 = 1003;
+pub const RPMTAG_SUMMARY: u32 = 1004;
+pub const RPMTAG_DESCRIPTION: u32 = 1005;
+pub const RPMTAG_BUILDTIME: u32 = 1006;
+pub const RPMTAG_BUILDHOST: u32 = 1007;
+pub const RPMTAG_SIZE: u32 = 1009;
+pub const RPMTAG_VENDOR: u32 = 1011;
+pub const RPMTAG_LICENSE: u32 = 1014;
+pub const RPMTAG_GROUP: u32 = 1016;
+pub const RPMTAG_URL: u32 = 1020;
+pub const RPMTAG_ARCH: u32 = 1022;
+pub const RPMTAG_FILEMODES: u32 = 1030;
+pub const RPMTAG_SOURCERPM: u32 = 1044;
+pub const RPMTAG_PROVIDENAME: u32 = 1047;
+pub const RPMTAG_REQUIREFLAGS: u32 = 1048;
+pub const RPMTAG_REQUIRENAME: u32 = 1049;
+pub const RPMTAG_REQUIREVERSION: u32 = 1050;
+pub const RPMTAG_CONFLICTFLAGS: u32 = 1053;
+pub const RPMTAG_CONFLICTNAME: u32 = 1054;
+pub const RPMTAG_CONFLICTVERSION: u32 = 1055;
+pub const RPMTAG_OBSOLETENAME: u32 = 1090;
+pub const RPMTAG_PROVIDEFLAGS: u32 = 1112;
+pub const RPMTAG_PROVIDEVERSION: u32 = 1113;
+pub const RPMTAG_OBSOLETEFLAGS: u32 = 1114;
+pub const RPMTAG_OBSOLETEVERSION: u32 = 1115;
+pub const RPMTAG_DIRINDEXES: u32 = 1116;
+pub const RPMTAG_BASENAMES: u32 = 1117;
+pub const RPMTAG_DIRNAMES: u32 = 1118;
+pub const RPMTAG_LONGSIZE: u32 = 5009;
+pub const RPMTAG_RECOMMENDNAME: u32 = 5046;
+pub const RPMTAG_RECOMMENDVERSION: u32 = 5047;
+pub const RPMTAG_RECOMMENDFLAGS: u32 = 5048;
+pub const RPMTAG_SUGGESTNAME: u32 = 5049;
+pub const RPMTAG_SUGGESTVERSION: u32 = 5050;
+pub const RPMTAG_SUGGESTFLAGS: u32 = 5051;
+pub const RPMTAG_SUPPLEMENTNAME: u32 = 5052;
+pub const RPMTAG_SUPPLEMENTVERSION: u32 = 5053;
+pub const RPMTAG_SUPPLEMENTFLAGS: u32 = 5054;
+pub const RPMTAG_ENHANCENAME: u32 = 5055;
+pub const RPMTAG_ENHANCEVERSION: u32 = 5056;
+pub const RPMTAG_ENHANCEFLAGS: u32 = 5057;
+
+// The types of the data in the header.
+const RPM_INT16_TYPE: u32 = 3;
+const RPM_INT32_TYPE: u32 = 4;
+const RPM_INT64_TYPE: u32 = 5;
+const RPM_STRING_TYPE: u32 = 6;
+const RPM_STRING_ARRAY_TYPE: u32 = 8;
+const RPM_I18NSTRING_TYPE: u32 = 9;
+
+// The bits of the dependency flags.
+pub const RPMSENSE_LESS: u32 = 1 << 1;
+pub const RPMSENSE_GREATER: u32 = 1 << 2;
+pub const RPMSENSE_EQUAL: u32 = 1 << 3;
+pub const RPMSENSE_RPMLIB: u32 = 1 << 24;
+
+// The limits of the header size, the same as hdrchkTags and hdrchkData of rpm, so a broken
+// header can not make it allocate too much memory.
+const MAX_INDEX_COUNT: usize = 0xffff;
+const MAX_STORE_SIZE: usize = 256 * 1024 * 1024;
+
+// Check the number of the index entries and the size of the data store read from a header.
+pub(crate) fn check_header_size(index_count: usize, store_size: usize) -> Result<()> {
+    if index_count > MAX_INDEX_COUNT {
+        return Err(anyhow!("too many tags in the rpm header: {}", index_count));
+    }
+    if store_size > MAX_STORE_SIZE {
+        return Err(anyhow!("the rpm header data is too large: {}", store_size));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    r#type: u32,
+    offset: usize,
+    count: usize,
+}
+
+// An rpm header, i.e. the index of the tags and the data store they point into, as stored
+// in the rpmdb and in .rpm files.
+#[derive(Debug)]
+pub struct Header {
+    index: HashMap<u32, IndexEntry>,
+    store: Vec<u8>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("the rpm header is truncated"))
+}
+
+impl Header {
+    // Parse the header blob starting with the number of the index entries and the size of the
+    // data store, without the magic of the header.
+    pub fn from_blob(blob: &[u8]) -> Result<Header> {
+        let index_count = read_u32(blob, 0)? as usize;
+        let store_size = read_u32(blob, 4)? as usize;
+        check_header_size(index_count, store_size)?;
+        let store_start = 8 + index_count * 16;
+        let store = blob
+            .get(store_start..store_start + store_size)
+            .ok_or_else(|| anyhow!("the rpm header is truncated"))?
+            .to_vec();
+        let mut index = HashMap::new();
+        for i in 0..index_count {
+            let base = 8 + i * 16;
+            let tag = read_u32(blob, base)?;
+            let entry = IndexEntry {
+                r#type: read_u32(blob, base + 4)?,
+                offset: read_u32(blob, base + 8)? as usize,
+                count: read_u32(blob, base + 12)? as usize,
+            };
+            if entry.offset > store.len() {
+                return Err(anyhow!("the data of tag {} is out of the rpm header", tag));
+            }
+            index.insert(tag, entry);
+        }
+        Ok(Header { index, store })
+    }
+
+    // Read the NUL terminated strings of the tag.
+    fn get_strings(&self, tag: u32) -> Result<Vec<String>> {
+        let entry = match self.index.get(&tag) {
+            Some(entry) => *entry,
+            None => return Ok(Vec::new()),
+        };
+        match entry.r#type {
+            RPM_STRING_TYPE | RPM_STRING_ARRAY_TYPE | RPM_I18NSTRING_TYPE => (),
+            _ => return Err(anyhow!("tag {} of the rpm header is not a string", tag)),
+        }
+        let mut strings = Vec::with_capacity(entry.count);
+        let mut offset = entry.offset;
+        for _ in 0..entry.count {
+            let len = self.store[offset..]
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| anyhow!("string of tag {} is not terminated", tag))?;
+            strings.push(String::from_utf8_lossy(&self.store[offset..offset + len]).to_string());
+            offset += len + 1;
+        }
+        Ok(strings)
+    }
+
+    // A single string, or the first one of an array, e.g. the untranslated summary.
+    pub fn get_string(&self, tag: u32) -> Result<Option<String>> {
+        Ok(self.get_strings(tag)?.into_iter().next())
+    }
+
+    pub fn get_string_array(&self, tag: u32) -> Result<Vec<String>> {
+        self.get_strings(tag)
+    }
+
+    // Integers of any size, widened to u64.
+    pub fn get_integers(&self, tag: u32) -> Result<Vec<u64>> {
+        let entry = match self.index.get(&tag) {
+            Some(entry) => *entry,
+            None => return Ok(Vec::new()),
+        };
+        let size = match entry.r#type {
+            RPM_INT16_TYPE => 2,
+            RPM_INT32_TYPE => 4,
+            RPM_INT64_TYPE => 8,
+            _ => return Err(anyhow!("tag {} of the rpm header is not an integer", tag)),
+        };
+        let bytes = self
+            .store
+            .get(entry.offset..entry.offset + size * entry.count)
+            .ok_or_else(|| anyhow!("the data of tag {} is out of the rpm header", tag))?;
+        Ok(bytes
+            .chunks(size)
+            .map(|chunk| chunk.iter().fold(0u64, |n, &b| (n << 8) | b as u64))
+            .collect())
+    }
+
+    pub fn get_integer(&self, tag: u32) -> Result<Option<u64>> {
+        Ok(self.get_integers(tag)?.into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_blob() -> Result<()> {
+        // NAME "bash", EPOCH 1 and DIRINDEXES [0, 1].
+        let store = b"bash\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\x01";
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&3u32.to_be_bytes());
+        blob.extend_from_slice(&(store.len() as u32).to_be_bytes());
+        for (tag, r#type, offset, count) in [
+            (RPMTAG_NAME, RPM_STRING_TYPE, 0u32, 1u32),
+            (RPMTAG_EPOCH, RPM_INT32_TYPE, 8, 1),
+            (RPMTAG_DIRINDEXES, RPM_INT32_TYPE, 12, 2),
+        ] {
+            for n in [tag, r#type, offset, count] {
+                blob.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+        blob.extend_from_slice(store);
+        let header = Header::from_blob(&blob)?;
+        assert_eq!(header.get_string(RPMTAG_NAME)?.as_deref(), Some("bash"));
+        assert_eq!(header.get_integer(RPMTAG_EPOCH)?, Some(1));
+        assert_eq!(header.get_integers(RPMTAG_DIRINDEXES)?, vec![0, 1]);
+        assert_eq!(header.get_string(RPMTAG_VERSION)?, None);
+        assert!(header.get_string(RPMTAG_EPOCH).is_err());
+        assert!(Header::from_blob(&blob[..blob.len() - 1]).is_err());
+        blob[..4].copy_from_slice(&0x10000u32.to_be_bytes());
+        assert!(Header::from_blob(&blob).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,20 @@
+pub mod cache;
+mod cardinality;
+pub mod checksum;
+mod compression;
 pub mod config;
+mod filelists;
+mod header;
+pub mod mirror;
+mod other;
+mod policy;
+pub mod problem;
 pub mod repo;
+pub mod repoclosure;
 mod repomd;
+mod richdep;
+mod rpmdb;
+mod rpmfile;
 pub mod solve;
 mod yum;
 mod version;
\ No newline at end of file
@@ -1,32 +1,160 @@
-use anyhow::Result;
-use rust_solv::{config, repo, solve};
+use anyhow::{bail, Result};
+use rust_solv::{config, mirror, repo, repoclosure, solve};
 use std::{env, path::Path};
 
+fn install(repo: &repo::Repo, options: &solve::SolveOptions, jobs: &[solve::Job]) {
+    let package_names = jobs
+        .iter()
+        .map(|job| match job {
+            solve::Job::Install(package_name) => package_name.clone(),
+            solve::Job::InstallPackage(package_id) => {
+                repo.get_package_by_id(*package_id).unwrap().get_nevra()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    // All the packages are solved together, as they are going to be installed together.
+    match solve::solve_jobs_in_repo(repo, jobs, options) {
+        Ok(solve::SolveResult::Satisfied(transaction)) => {
+            println!(
+                "Congratulations! Package {}'s dependencies can be satisfied in the repo. :)",
+                package_names
+            );
+            for item in transaction.get_items() {
+                println!("  - {} ({})", item.nevra, item.repo_id);
+            }
+        }
+        Ok(solve::SolveResult::Unsatisfied(problems)) => {
+            println!(
+                "Sorry, package {}'s dependencies can not be satisfied in the repo. :(",
+                package_names
+            );
+            for problem in problems {
+                println!("  - {}", problem);
+            }
+        }
+        Ok(solve::SolveResult::MissingProviders(problems)) => {
+            println!("Sorry, package {}'s dependencies can not be satisfied in the repo, some of them are not provided by any package. :(", package_names);
+            for problem in problems {
+                println!("  - {}", problem);
+            }
+        }
+        Ok(solve::SolveResult::PackageNotFound(package_name)) => {
+            println!("Error: package {} not found in the repo. :(", package_name)
+        }
+        Err(_) => println!("Error: something wrong happened while solving. :("),
+    }
+}
+
+fn check_repo_closure(repo: &repo::Repo, options: &solve::SolveOptions) -> Result<()> {
+    let report = repoclosure::check_repo_closure_parallel(repo, options)?;
+    for (reason, packages) in &report.reasons {
+        println!("{}:", reason);
+        for package in packages {
+            println!("  - {}", package);
+        }
+    }
+    println!(
+        "{} of {} packages in the repo can not be installed.",
+        report.broken.len(),
+        report.checked
+    );
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let packages: Vec<String> = env::args()
-        .enumerate()
-        .filter(|&(i, _)| i > 0)
-        .map(|(_, v)| v)
-        .collect();
-    if packages.is_empty() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let repoclosure = args.first().map(String::as_str) == Some("repoclosure");
+    if repoclosure {
+        args.remove(0);
+    }
+    let mut packages: Vec<String> = Vec::new();
+    let mut minimize = false;
+    let mut offline = false;
+    let mut installroot: Option<String> = None;
+    let mut installed = false;
+    for arg in args {
+        match arg.as_str() {
+            "--minimize" => minimize = true,
+            "--offline" => offline = true,
+            "--installed" => installed = true,
+            _ => match arg.strip_prefix("--installroot=") {
+                Some(dir) => installroot = Some(dir.to_string()),
+                None => packages.push(arg),
+            },
+        }
+    }
+    if packages.is_empty() && !repoclosure {
         panic!("Package name not found!");
     } else {
+        // The repos of dnf are used with --installroot, or if there is no config of rust-solv.
         let config_path_str = std::env::var("HOME")? + "/.config/rust-solv/config.toml";
-        let cfg = config::Config::from_file(Path::new(&config_path_str))?;
-        if let Some(repo_baseurl) = cfg.get_repo_baseurl() {
-            let repo = repo::Repo::from_baseurl(repo_baseurl)?;
-            for package_name in packages {
-                match solve::check_package_satisfiability_in_repo(&repo, &package_name) {
-                    Ok(solve::ReturnValue::Satisfied) => println!("Congratulations! Package {}'s dependencies can be satisfied in the repo. :)", package_name),
-                    Ok(solve::ReturnValue::Unsatisfied) => println!("Sorry, package {}'s dependencies can not be satisfied in the repo. :(", package_name),
-                    Ok(solve::ReturnValue::VersionConflict) => println!("Sorry, package {}'s dependencies can not be satisfied in the repo. (version conflict) :(", package_name),
-                    Ok(solve::ReturnValue::PackageNotFound) => println!("Error: package {} not found in the repo. :(", package_name),
-                    Err(_) => println!("Error: something wrong happened while solving. :("),
-                }
+        let cfg = match &installroot {
+            Some(installroot) => config::Config::from_dnf_conf(Path::new(installroot))?,
+            None if !Path::new(&config_path_str).exists() => {
+                config::Config::from_dnf_conf(Path::new("/"))?
             }
-            Ok(())
+            None => config::Config::from_file(Path::new(&config_path_str))?,
+        };
+        // All the repos in the config are merged into one, so the dependencies can be
+        // satisfied across them.
+        let mut repo = repo::Repo::default();
+        // With --installed, the packages installed on the system are kept while solving.
+        if installed {
+            let installroot = installroot.as_deref().unwrap_or("/");
+            repo.merge(repo::Repo::from_rpmdb(Path::new(installroot))?);
+        }
+        for repoinfo in cfg.get_repoinfos() {
+            if !repoinfo.is_enabled() {
+                continue;
+            }
+            // The baseurl is preferred, then the metalink and then the mirrorlist.
+            let mirrors = match (
+                repoinfo.get_baseurls(),
+                repoinfo.get_metalink(),
+                repoinfo.get_mirrorlist(),
+            ) {
+                (baseurls @ [_, ..], _, _) => mirror::Mirrors::from_baseurls(baseurls),
+                ([], Some(metalink), _) => mirror::Mirrors::from_metalink(metalink),
+                ([], None, Some(mirrorlist)) => mirror::Mirrors::from_mirrorlist(mirrorlist),
+                ([], None, None) => bail!(
+                    "Repo {} has no baseurl, metalink or mirrorlist, please check the config file",
+                    repoinfo.get_id()
+                ),
+            };
+            let mut cache = cfg.get_metadata_cache(repoinfo)?;
+            if offline {
+                cache.set_offline(true);
+            }
+            let mut repo_to_merge = repo::Repo::from_mirrors_with_cache(mirrors, Some(cache))?;
+            repo_to_merge.set_repo_id(&repoinfo.get_id());
+            if let Some(priority) = repoinfo.get_priority() {
+                repo_to_merge.set_priority(priority);
+            }
+            if let Some(cost) = repoinfo.get_cost() {
+                repo_to_merge.set_cost(cost);
+            }
+            repo_to_merge.filter_packages(&cfg.get_excludes(repoinfo), repoinfo.get_includepkgs());
+            repo.merge(repo_to_merge);
+        }
+        // The local .rpm files are installed as they are, not the packages with the same name.
+        let (rpm_paths, package_names): (Vec<String>, Vec<String>) = packages
+            .into_iter()
+            .partition(|package| package.ends_with(".rpm"));
+        let mut jobs: Vec<solve::Job> =
+            package_names.into_iter().map(solve::Job::Install).collect();
+        if !rpm_paths.is_empty() {
+            let first_id = repo.get_package_count();
+            repo.merge(repo::Repo::from_rpm_files(&rpm_paths)?);
+            jobs.extend((first_id..repo.get_package_count()).map(solve::Job::InstallPackage));
+        }
+        let mut options = solve::SolveOptions::from_config(&cfg);
+        options.minimize = minimize;
+        if repoclosure {
+            check_repo_closure(&repo, &options)
         } else {
-            panic!("Repo baseurl not found! Please check the config file!");
+            install(&repo, &options, &jobs);
+            Ok(())
         }
     }
 }
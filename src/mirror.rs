@@ -0,0 +1,405 @@
+use crate::checksum::{verify_checksum, ChecksumType};
+use crate::yum::YumVariables;
+use anyhow::{anyhow, Context, Result};
+use quick_xml::events::Event;
+use reqwest::Url;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const REPOMD_PATH: &str = "repodata/repomd.xml";
+
+// Where the base urls of a repo come from, like the baseurl, mirrorlist and metalink options of dnf.
+#[derive(Debug)]
+enum MirrorSource {
+    Baseurls(Vec<String>),
+    Mirrorlist(String),
+    Metalink(String),
+}
+
+#[derive(Debug, Default)]
+struct ResolvedMirrors {
+    baseurls: Vec<String>,
+    // The checksums repomd.xml is expected to match, one set for the current repomd.xml
+    // and one for each of the alternates still on the mirrors.
+    repomd_checksums: Vec<Vec<(ChecksumType, String)>>,
+}
+
+// The mirrors of a repo. The mirrorlist or metalink is only downloaded the first time
+// the repo itself is, so the cached metadata can be used offline.
+#[derive(Debug)]
+pub struct Mirrors {
+    source: MirrorSource,
+    resolved: OnceLock<ResolvedMirrors>,
+}
+
+// The path of a file:// url or a plain path, None for remote urls. The file:// url is decoded,
+// e.g. file://localhost/srv/my%20repo/ is /srv/my repo/.
+fn get_local_path(url: &str) -> Option<PathBuf> {
+    if !url.contains("://") {
+        return Some(PathBuf::from(url));
+    }
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().ok(),
+        _ => None,
+    }
+}
+
+// Get the file from a remote url, or a local one given by a file:// url or a plain path.
+pub(crate) fn download(url: &str) -> Result<Vec<u8>> {
+    if let Some(path) = get_local_path(url) {
+        return fs::read(&path).with_context(|| format!("Failed to read {:?}", path));
+    }
+    let response = reqwest::blocking::get(url)
+        .with_context(|| format!("Failed to connect to {:?}", url))?
+        .error_for_status()?;
+    Ok(response.bytes()?.to_vec())
+}
+
+// Open the file at a remote url, or a local one, to be read as a stream.
+fn open(url: &str) -> Result<Box<dyn BufRead>> {
+    if let Some(path) = get_local_path(url) {
+        let file = File::open(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        return Ok(Box::new(BufReader::new(file)));
+    }
+    let response = reqwest::blocking::get(url)
+        .with_context(|| format!("Failed to connect to {:?}", url))?
+        .error_for_status()?;
+    Ok(Box::new(BufReader::new(response)))
+}
+
+// Append a slash to the base url, and replace the yum variables like $basearch in it.
+fn normalize_url(url: &str, directory: bool) -> Result<String> {
+    let url = url.trim().to_string();
+    let url = if directory && !url.ends_with('/') {
+        url + "/"
+    } else {
+        url
+    };
+    if url.contains('$') {
+        YumVariables::new()?.replace_yum_variables(url)
+    } else {
+        Ok(url)
+    }
+}
+
+// A mirrorlist is a plain list of base urls, one per line, where # starts a comment.
+fn parse_mirrorlist(mirrorlist: &str) -> Vec<String> {
+    mirrorlist
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+// Parse the metalink of repomd.xml, i.e. its checksums and the urls of the mirrors providing it,
+// the mirrors with the higher preference first.
+fn parse_metalink(metalink_xml: &str) -> Result<ResolvedMirrors> {
+    let mut reader = quick_xml::Reader::from_str(metalink_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut resolved = ResolvedMirrors::default();
+    let mut urls: Vec<(i32, String)> = Vec::new();
+    let mut in_repomd = false;
+    // The hash type, or the preference of the url, whose text is being read.
+    let mut hash_type: Option<String> = None;
+    let mut url_preference: Option<i32> = None;
+    loop {
+        match reader
+            .read_event(&mut buf)
+            .with_context(|| "Failed to parse the metalink")?
+        {
+            Event::Start(e) => match e.local_name() {
+                b"file" => {
+                    in_repomd = e
+                        .attributes()
+                        .flatten()
+                        .any(|attr| attr.key == b"name" && attr.value.as_ref() == b"repomd.xml");
+                }
+                b"verification" if in_repomd => resolved.repomd_checksums.push(Vec::new()),
+                b"hash" if in_repomd => {
+                    hash_type = e
+                        .attributes()
+                        .flatten()
+                        .find(|attr| attr.key == b"type")
+                        .map(|attr| String::from_utf8_lossy(&attr.value).to_string());
+                }
+                b"url" if in_repomd => {
+                    let preference = e
+                        .attributes()
+                        .flatten()
+                        .find(|attr| attr.key == b"preference")
+                        .and_then(|attr| String::from_utf8_lossy(&attr.value).parse().ok());
+                    url_preference = Some(preference.unwrap_or(0));
+                }
+                _ => (),
+            },
+            Event::Text(e) => {
+                let text = String::from_utf8(e.unescaped()?.to_vec())?;
+                if let Some(hash_type) = hash_type.take() {
+                    // Hashes of the types not supported, e.g. md5, are ignored.
+                    if let (Ok(checksum_type), Some(checksums)) = (
+                        hash_type.parse::<ChecksumType>(),
+                        resolved.repomd_checksums.last_mut(),
+                    ) {
+                        checksums.push((checksum_type, text.trim().to_string()));
+                    }
+                } else if let Some(preference) = url_preference.take() {
+                    // Only the mirrors of the protocols which can be downloaded from are used.
+                    let supported = ["http://", "https://", "file://"]
+                        .iter()
+                        .any(|protocol| text.starts_with(protocol));
+                    if let Some(baseurl) = text.strip_suffix(REPOMD_PATH) {
+                        if supported {
+                            urls.push((preference, baseurl.to_string()));
+                        }
+                    }
+                }
+            }
+            Event::End(e) => match e.local_name() {
+                b"file" => in_repomd = false,
+                b"hash" => hash_type = None,
+                b"url" => url_preference = None,
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    resolved
+        .repomd_checksums
+        .retain(|checksums| !checksums.is_empty());
+    // The sort is stable, so the mirrors with the same preference are kept in order.
+    urls.sort_by(|(x, _), (y, _)| y.cmp(x));
+    resolved.baseurls = urls.into_iter().map(|(_, url)| url).collect();
+    Ok(resolved)
+}
+
+impl Mirrors {
+    pub fn from_baseurl(baseurl: &str) -> Mirrors {
+        Mirrors::from_baseurls(&[baseurl.to_string()])
+    }
+
+    // Several baseurls of a repo are tried in order, like the mirrors in a mirrorlist.
+    pub fn from_baseurls(baseurls: &[String]) -> Mirrors {
+        Mirrors::new(MirrorSource::Baseurls(baseurls.to_vec()))
+    }
+
+    pub fn from_mirrorlist(mirrorlist_url: &str) -> Mirrors {
+        Mirrors::new(MirrorSource::Mirrorlist(mirrorlist_url.to_string()))
+    }
+
+    pub fn from_metalink(metalink_url: &str) -> Mirrors {
+        Mirrors::new(MirrorSource::Metalink(metalink_url.to_string()))
+    }
+
+    fn new(source: MirrorSource) -> Mirrors {
+        Mirrors {
+            source,
+            resolved: OnceLock::new(),
+        }
+    }
+
+    // The metadata of local repos is read directly, without being cached.
+    pub fn is_local(&self) -> bool {
+        match &self.source {
+            MirrorSource::Baseurls(baseurls) => baseurls
+                .iter()
+                .all(|baseurl| get_local_path(baseurl).is_some()),
+            _ => false,
+        }
+    }
+
+    fn resolve(&self) -> Result<ResolvedMirrors> {
+        let mut resolved = match &self.source {
+            MirrorSource::Baseurls(baseurls) => ResolvedMirrors {
+                baseurls: baseurls.clone(),
+                ..Default::default()
+            },
+            MirrorSource::Mirrorlist(url) => {
+                let mirrorlist = download(&normalize_url(url, false)?)
+                    .with_context(|| format!("Failed to get the mirrorlist {}", url))?;
+                ResolvedMirrors {
+                    baseurls: parse_mirrorlist(&String::from_utf8(mirrorlist)?),
+                    ..Default::default()
+                }
+            }
+            MirrorSource::Metalink(url) => {
+                let metalink = download(&normalize_url(url, false)?)
+                    .with_context(|| format!("Failed to get the metalink {}", url))?;
+                parse_metalink(&String::from_utf8(metalink)?)?
+            }
+        };
+        resolved.baseurls = resolved
+            .baseurls
+            .iter()
+            .map(|baseurl| normalize_url(baseurl, true))
+            .collect::<Result<_>>()?;
+        if resolved.baseurls.is_empty() {
+            return Err(anyhow!("No mirror found in {}", self));
+        }
+        Ok(resolved)
+    }
+
+    fn get_resolved(&self) -> Result<&ResolvedMirrors> {
+        if let Some(resolved) = self.resolved.get() {
+            return Ok(resolved);
+        }
+        let resolved = self.resolve()?;
+        Ok(self.resolved.get_or_init(|| resolved))
+    }
+
+    // Read the file at the path relative to the base url with the given function, which also
+    // verifies it, from the first mirror it succeeds with. So a mirror which is unreachable
+    // or out of sync fails over to the next one, while the file is streamed from each of them.
+    pub(crate) fn read<T>(
+        &self,
+        path: &str,
+        mut read: impl FnMut(Box<dyn BufRead>) -> Result<T>,
+    ) -> Result<T> {
+        let mut last_error = None;
+        for baseurl in &self.get_resolved()?.baseurls {
+            match open(&(baseurl.clone() + path)).and_then(&mut read) {
+                Ok(result) => return Ok(result),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        let error = last_error.unwrap_or_else(|| anyhow!("No mirror found"));
+        Err(error.context(format!(
+            "Failed to get {} from the mirrors of {}",
+            path, self
+        )))
+    }
+
+    // Download the file at the path relative to the base url, from the first mirror it can be
+    // downloaded from and verified by the given function.
+    pub(crate) fn download(
+        &self,
+        path: &str,
+        verify: impl Fn(&[u8]) -> Result<()>,
+    ) -> Result<Vec<u8>> {
+        self.read(path, |mut reader| {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            verify(&bytes)?;
+            Ok(bytes)
+        })
+    }
+
+    // Download repomd.xml, which has to match one of the sets of checksums in the metalink.
+    pub(crate) fn download_repomd(&self) -> Result<Vec<u8>> {
+        let repomd_checksums = &self.get_resolved()?.repomd_checksums;
+        self.download(REPOMD_PATH, |repomd_xml| {
+            if repomd_checksums.is_empty() {
+                return Ok(());
+            }
+            let matched = repomd_checksums.iter().any(|checksums| {
+                checksums.iter().all(|(checksum_type, expected)| {
+                    verify_checksum(REPOMD_PATH, repomd_xml, *checksum_type, expected).is_ok()
+                })
+            });
+            if matched {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "{} does not match the metalink of {}",
+                    REPOMD_PATH,
+                    self
+                ))
+            }
+        })
+    }
+}
+
+impl fmt::Display for Mirrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            MirrorSource::Baseurls(urls) => write!(f, "{}", urls.join(", ")),
+            MirrorSource::Mirrorlist(url) | MirrorSource::Metalink(url) => {
+                write!(f, "{}", url)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_local_path() {
+        for (url, path) in [
+            ("/srv/repo/", Some("/srv/repo/")),
+            ("file:///srv/repo/", Some("/srv/repo/")),
+            ("file://localhost/srv/repo/", Some("/srv/repo/")),
+            ("file:///srv/my%20repo/", Some("/srv/my repo/")),
+            ("https://example.com/repo/", None),
+            ("file://example.com/srv/repo/", None),
+        ] {
+            assert_eq!(get_local_path(url), path.map(PathBuf::from), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_parse_mirrorlist() {
+        let mirrorlist =
+            "# repo = OS\nhttp://mirror1.example.com/OS/\n\n  https://mirror2.example.com/OS/  \n";
+        assert_eq!(
+            parse_mirrorlist(mirrorlist),
+            vec![
+                "http://mirror1.example.com/OS/",
+                "https://mirror2.example.com/OS/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_metalink() -> Result<()> {
+        let metalink = r#"<?xml version="1.0" encoding="utf-8"?>
+<metalink version="3.0" xmlns="http://www.metalinker.org/" xmlns:mm0="http://fedorahosted.org/mirrormanager">
+  <files>
+    <file name="repomd.xml">
+      <mm0:timestamp>1650000000</mm0:timestamp>
+      <size>3000</size>
+      <verification>
+        <hash type="md5">a0b1c2</hash>
+        <hash type="sha256">abc</hash>
+      </verification>
+      <mm0:alternates>
+        <mm0:alternate>
+          <mm0:timestamp>1640000000</mm0:timestamp>
+          <verification>
+            <hash type="sha256">def</hash>
+          </verification>
+        </mm0:alternate>
+      </mm0:alternates>
+      <resources maxconnections="1">
+        <url protocol="rsync" type="rsync" location="CN" preference="100">rsync://mirror0.example.com/OS/repodata/repomd.xml</url>
+        <url protocol="https" type="https" location="CN" preference="90">https://mirror1.example.com/OS/repodata/repomd.xml</url>
+        <url protocol="http" type="http" location="US" preference="99">http://mirror2.example.com/OS/repodata/repomd.xml</url>
+      </resources>
+    </file>
+  </files>
+</metalink>"#;
+        let resolved = parse_metalink(metalink)?;
+        assert_eq!(
+            resolved.baseurls,
+            vec![
+                "http://mirror2.example.com/OS/",
+                "https://mirror1.example.com/OS/"
+            ]
+        );
+        assert_eq!(
+            resolved.repomd_checksums,
+            vec![
+                vec![(ChecksumType::Sha256, "abc".to_string())],
+                vec![(ChecksumType::Sha256, "def".to_string())]
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use crate::repo::Version;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Changelog {
+    pub author: String,
+    // Seconds since the epoch.
+    pub date: u64,
+    #[serde(rename = "$value", default)]
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OtherPackage {
+    pub pkgid: String,
+    pub name: String,
+    pub arch: String,
+    pub version: Version,
+    #[serde(rename = "changelog", default)]
+    pub changelogs: Vec<Changelog>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Otherdata {
+    #[serde(rename = "package", default)]
+    pub packages: Vec<OtherPackage>,
+}
+
+impl Otherdata {
+    pub fn from_str(other_xml: &str) -> Result<Otherdata> {
+        quick_xml::de::from_str(other_xml).with_context(|| "Failed to parse other.xml")
+    }
+}
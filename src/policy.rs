@@ -0,0 +1,74 @@
+use crate::repo::{IdT, Repo};
+use crate::solve::SolveOptions;
+use crate::version::evr_compare;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// The architectures of the host, the best first.
+pub fn get_default_arches() -> Vec<String> {
+    let arches: &[&str] = match std::env::consts::ARCH {
+        "x86_64" => &["x86_64", "noarch", "i686", "i586", "i386"],
+        "x86" => &["i686", "noarch", "i586", "i386"],
+        arch => &[arch, "noarch"],
+    };
+    arches.iter().map(|arch| arch.to_string()).collect()
+}
+
+fn get_arch_rank(repo: &Repo, options: &SolveOptions, package_id: IdT) -> usize {
+    let arch = repo.get_package_by_id(package_id).unwrap().get_arch();
+    options
+        .arches
+        .iter()
+        .position(|a| a == arch)
+        .unwrap_or(options.arches.len())
+}
+
+// Compare two packages with the same name: the installed one, the higher EVR, the better arch
+// and then the higher repo priority (the smaller value) and the smaller repo cost come first.
+fn compare_same_name(repo: &Repo, options: &SolveOptions, x: IdT, y: IdT) -> Ordering {
+    let (px, py) = (
+        repo.get_package_by_id(x).unwrap(),
+        repo.get_package_by_id(y).unwrap(),
+    );
+    repo.is_package_installed(y)
+        .cmp(&repo.is_package_installed(x))
+        .then_with(|| {
+            evr_compare(&py.get_evr_entry(), &px.get_evr_entry()).unwrap_or(Ordering::Equal)
+        })
+        .then_with(|| get_arch_rank(repo, options, x).cmp(&get_arch_rank(repo, options, y)))
+        .then_with(|| {
+            repo.get_package_priority(x)
+                .cmp(&repo.get_package_priority(y))
+        })
+        .then_with(|| repo.get_package_cost(x).cmp(&repo.get_package_cost(y)))
+        .then(x.cmp(&y))
+}
+
+// Sort the candidates of a job or a requirement, the best first.
+// Versions are only comparable between packages with the same name, so the candidates
+// are grouped by name, each group is sorted, and the groups are ordered by their best
+// package's installed state, arch, repo priority, repo cost and name.
+pub fn sort_candidates(repo: &Repo, options: &SolveOptions, candidates: &mut Vec<IdT>) {
+    let mut groups: HashMap<&String, Vec<IdT>> = HashMap::new();
+    for &id in candidates.iter() {
+        let name = repo.get_package_by_id(id).unwrap().get_name();
+        groups.entry(name).or_default().push(id);
+    }
+    let mut groups: Vec<(&String, Vec<IdT>)> = groups.into_iter().collect();
+    for (_, ids) in groups.iter_mut() {
+        ids.sort_by(|&x, &y| compare_same_name(repo, options, x, y));
+    }
+    groups.sort_by(|(x_name, x_ids), (y_name, y_ids)| {
+        let (x, y) = (x_ids[0], y_ids[0]);
+        repo.is_package_installed(y)
+            .cmp(&repo.is_package_installed(x))
+            .then_with(|| get_arch_rank(repo, options, x).cmp(&get_arch_rank(repo, options, y)))
+            .then_with(|| {
+                repo.get_package_priority(x)
+                    .cmp(&repo.get_package_priority(y))
+            })
+            .then_with(|| repo.get_package_cost(x).cmp(&repo.get_package_cost(y)))
+            .then_with(|| x_name.cmp(y_name))
+    });
+    *candidates = groups.into_iter().flat_map(|(_, ids)| ids).collect();
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+// A rule which takes part in making the requested packages uninstallable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    // No package provides the entry (with a satisfying version) required by the package.
+    NothingProvides {
+        package: String,
+        entry: String,
+    },
+    // The entry is provided, but none of its providers can be installed.
+    RequiresUninstallable {
+        package: String,
+        entry: String,
+    },
+    Conflicts {
+        package: String,
+        entry: String,
+        conflicting: String,
+    },
+    Obsoletes {
+        package: String,
+        entry: String,
+        obsoleted: String,
+    },
+    // Two packages with the same name can not be installed together.
+    SameName {
+        package: String,
+        other: String,
+    },
+    // The installed package can neither be kept nor upgraded.
+    Installed {
+        package: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::NothingProvides { package, entry } => {
+                write!(f, "nothing provides {} needed by {}", entry, package)
+            }
+            Problem::RequiresUninstallable { package, entry } => write!(
+                f,
+                "package {} requires {}, but none of the providers can be installed",
+                package, entry
+            ),
+            Problem::Conflicts {
+                package,
+                entry,
+                conflicting,
+            } => write!(
+                f,
+                "package {} conflicts with {} provided by {}",
+                package, entry, conflicting
+            ),
+            Problem::SameName { package, other } => {
+                write!(f, "cannot install both {} and {}", package, other)
+            }
+            Problem::Installed { package } => write!(
+                f,
+                "installed package {} can not be kept, upgraded or replaced",
+                package
+            ),
+            Problem::Obsoletes {
+                package,
+                entry,
+                obsoleted,
+            } => write!(
+                f,
+                "package {} is obsoleted by {} (obsoletes {})",
+                obsoleted, package, entry
+            ),
+        }
+    }
+}
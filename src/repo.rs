@@ -1,13 +1,25 @@
-use crate::repomd::Repomd;
+use crate::cache::MetadataCache;
+use crate::filelists::{FileEntry, Filelists};
+use crate::header::*;
+use crate::mirror::Mirrors;
+pub use crate::other::Changelog;
+use crate::other::Otherdata;
+use crate::repomd::{MetadataLoader, Repomd};
+use crate::rpmdb::read_rpmdb_headers;
+use crate::rpmfile::read_rpm_file;
 use crate::version::{version_compare, Flag};
-use crate::yum::YumVariables;
 use anyhow::{anyhow, Context, Result};
-use quick_xml;
+use quick_xml::events::{BytesStart, Event};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Version {
+// The version of a package, shared by primary.xml and the other metadata files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Version {
     epoch: i32,
     ver: String,
     rel: String,
@@ -22,65 +34,743 @@ pub struct RpmEntry {
     pub rel: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Entries {
     #[serde(rename = "entry")]
     entries: Vec<RpmEntry>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Checksum {
+    #[serde(rename = "type")]
+    pub checksum_type: String,
+    pub pkgid: Option<String>,
+    #[serde(rename = "$value")]
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Time {
+    pub file: u64,
+    pub build: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Size {
+    pub package: u64,
+    pub installed: u64,
+    pub archive: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Location {
+    pub href: String,
+}
+
+// The byte range of the rpm header in the package file.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct HeaderRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Format {
+    license: Option<String>,
+    vendor: Option<String>,
+    group: Option<String>,
+    buildhost: Option<String>,
+    sourcerpm: Option<String>,
+    #[serde(rename = "header-range")]
+    header_range: Option<HeaderRange>,
     provides: Option<Entries>,
     requires: Option<Entries>,
     conflicts: Option<Entries>,
     obsoletes: Option<Entries>,
+    // The weak dependencies, which are installed if possible, but never cause a failure.
+    recommends: Option<Entries>,
+    suggests: Option<Entries>,
+    supplements: Option<Entries>,
+    enhances: Option<Entries>,
+    // Only the commonly required files, e.g. those in /etc and the bin directories,
+    // are listed in primary.xml. The others are listed in filelists.xml.
+    #[serde(rename = "file", default)]
+    files: Vec<FileEntry>,
 }
 
 pub type IdT = usize;
 
-#[derive(Debug, Serialize, Deserialize)]
+// Packages from the repo with the smaller priority value are preferred, the same as dnf.
+pub const DEFAULT_PRIORITY: i32 = 99;
+
+// Among the repos with the same priority, packages from the repo with the smaller cost are preferred.
+pub const DEFAULT_COST: i32 = 1000;
+
+pub const INSTALLED_REPO_ID: &str = "@System";
+
+pub const COMMANDLINE_REPO_ID: &str = "@commandline";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Package {
     name: String,
+    arch: String,
     version: Version,
+    checksum: Option<Checksum>,
+    summary: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+    time: Option<Time>,
+    size: Option<Size>,
+    location: Option<Location>,
     format: Format,
+    // Loaded from other.xml by Repo::load_changelogs.
+    #[serde(skip)]
+    changelogs: OnceLock<Vec<Changelog>>,
+    // The index of the repo the package comes from, in the origins of the merged repo.
+    #[serde(skip)]
+    origin: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// A repo merged into the pool, where some of the packages come from.
+#[derive(Debug)]
+struct RepoOrigin {
+    id: String,
+    priority: i32,
+    cost: i32,
+    // The packages of the origin are installed on the system.
+    installed: bool,
+    filelists_loader: Option<MetadataLoader>,
+    other_loader: Option<MetadataLoader>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Repo {
     #[serde(rename = "package")]
     packages: Vec<Package>,
     #[serde(skip)]
     providers: HashMap<String, Vec<IdT>>,
+    #[serde(skip)]
+    origins: Vec<RepoOrigin>,
+    // The providers of every file path in filelists.xml, built on the first file dependency
+    // which can not be resolved by primary.xml, or the error of loading filelists.xml.
+    #[serde(skip)]
+    file_providers: OnceLock<Result<HashMap<String, Vec<IdT>>>>,
+    #[serde(skip)]
+    changelogs_loaded: OnceLock<()>,
+}
+
+// Finds the package in primary.xml described by an entry of filelists.xml or other.xml.
+// Packages are matched by pkgid, which is the checksum of the package in primary.xml,
+// and by name, arch and EVR when there is no checksum.
+struct PackageMatcher {
+    ids_by_pkgid: HashMap<String, IdT>,
+    ids_by_nevra: HashMap<String, IdT>,
+}
+
+impl PackageMatcher {
+    // Only the packages from the given origin are matched, as the metadata is per repo.
+    fn new(packages: &[Package], origin: usize) -> PackageMatcher {
+        let mut matcher = PackageMatcher {
+            ids_by_pkgid: HashMap::new(),
+            ids_by_nevra: HashMap::new(),
+        };
+        for (id, package) in packages.iter().enumerate() {
+            if package.origin != origin {
+                continue;
+            }
+            if let Some(checksum) = &package.checksum {
+                matcher.ids_by_pkgid.insert(checksum.value.clone(), id);
+            }
+            matcher.ids_by_nevra.insert(package.get_nevra(), id);
+        }
+        matcher
+    }
+
+    fn find(
+        &self,
+        pkgid: &str,
+        name: &str,
+        epoch: i32,
+        ver: &str,
+        rel: &str,
+        arch: &str,
+    ) -> Option<IdT> {
+        self.ids_by_pkgid.get(pkgid).copied().or_else(|| {
+            let nevra = Package::format_nevra(name, epoch, ver, rel, arch);
+            self.ids_by_nevra.get(&nevra).copied()
+        })
+    }
+}
+
+// The elements of primary.xml whose text is kept.
+enum TextField {
+    Name,
+    Arch,
+    Checksum,
+    Summary,
+    Description,
+    Url,
+    License,
+    Vendor,
+    Group,
+    Buildhost,
+    Sourcerpm,
+    File,
+}
+
+#[derive(Clone, Copy)]
+enum EntriesKind {
+    Provides,
+    Requires,
+    Conflicts,
+    Obsoletes,
+    Recommends,
+    Suggests,
+    Supplements,
+    Enhances,
+}
+
+impl Format {
+    fn get_entries_mut(&mut self, kind: EntriesKind) -> &mut Entries {
+        let entries = match kind {
+            EntriesKind::Provides => &mut self.provides,
+            EntriesKind::Requires => &mut self.requires,
+            EntriesKind::Conflicts => &mut self.conflicts,
+            EntriesKind::Obsoletes => &mut self.obsoletes,
+            EntriesKind::Recommends => &mut self.recommends,
+            EntriesKind::Suggests => &mut self.suggests,
+            EntriesKind::Supplements => &mut self.supplements,
+            EntriesKind::Enhances => &mut self.enhances,
+        };
+        entries.get_or_insert_with(Entries::default)
+    }
+}
+
+// Reads primary.xml with the event reader of quick-xml and yields one package at a time.
+struct PrimaryParser<R: BufRead> {
+    reader: quick_xml::Reader<R>,
+    buf: Vec<u8>,
+    // Whether the root element, which has to be <metadata>, has been read.
+    has_root: bool,
+}
+
+fn get_attributes(element: &BytesStart) -> Result<HashMap<String, String>> {
+    let mut attributes = HashMap::new();
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8(attribute.key.to_vec())?;
+        let value = String::from_utf8(attribute.unescaped_value()?.into_owned())?;
+        attributes.insert(key, value);
+    }
+    Ok(attributes)
+}
+
+fn parse_attribute<T>(attributes: &HashMap<String, String>, key: &str) -> Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match attributes.get(key) {
+        Some(value) => Ok(Some(value.parse().with_context(|| {
+            format!("Invalid value {:?} of attribute {}", value, key)
+        })?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_required_attribute<T>(attributes: &HashMap<String, String>, key: &str) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    parse_attribute(attributes, key)?.ok_or_else(|| anyhow!("Attribute {} not found", key))
+}
+
+impl<R: BufRead> PrimaryParser<R> {
+    fn new(reader: R) -> PrimaryParser<R> {
+        let mut reader = quick_xml::Reader::from_reader(reader);
+        reader.trim_text(true);
+        PrimaryParser {
+            reader,
+            buf: Vec::new(),
+            has_root: false,
+        }
+    }
+
+    fn next_package(&mut self) -> Result<Option<Package>> {
+        let mut package: Option<Package> = None;
+        // The dependency list the entries are added to, e.g. provides or requires.
+        let mut entries_kind: Option<EntriesKind> = None;
+        let mut text_field: Option<(TextField, HashMap<String, String>)> = None;
+        let mut text = String::new();
+        loop {
+            self.buf.clear();
+            let event = self.reader.read_event(&mut self.buf).with_context(|| {
+                format!("XML error at position {}", self.reader.buffer_position())
+            })?;
+            match event {
+                Event::Start(ref element) | Event::Empty(ref element) => {
+                    let is_empty = matches!(event, Event::Empty(_));
+                    let name = element.local_name();
+                    if !self.has_root {
+                        if name != b"metadata" {
+                            return Err(anyhow!(
+                                "The root element is <{}> instead of <metadata>",
+                                String::from_utf8_lossy(name)
+                            ));
+                        }
+                        self.has_root = true;
+                        continue;
+                    }
+                    if name == b"package" {
+                        package = Some(Package::default());
+                        if is_empty {
+                            return Ok(package);
+                        }
+                        continue;
+                    }
+                    let package = match package.as_mut() {
+                        Some(package) => package,
+                        None => continue,
+                    };
+                    let attributes = get_attributes(element)?;
+                    let field = match name {
+                        b"name" => Some(TextField::Name),
+                        b"arch" => Some(TextField::Arch),
+                        b"checksum" => Some(TextField::Checksum),
+                        b"summary" => Some(TextField::Summary),
+                        b"description" => Some(TextField::Description),
+                        b"url" => Some(TextField::Url),
+                        b"license" => Some(TextField::License),
+                        b"vendor" => Some(TextField::Vendor),
+                        b"group" => Some(TextField::Group),
+                        b"buildhost" => Some(TextField::Buildhost),
+                        b"sourcerpm" => Some(TextField::Sourcerpm),
+                        b"file" => Some(TextField::File),
+                        b"version" => {
+                            package.version = Version {
+                                epoch: parse_attribute(&attributes, "epoch")?.unwrap_or(0),
+                                ver: parse_required_attribute(&attributes, "ver")?,
+                                rel: parse_required_attribute(&attributes, "rel")?,
+                            };
+                            None
+                        }
+                        b"time" => {
+                            package.time = Some(Time {
+                                file: parse_required_attribute(&attributes, "file")?,
+                                build: parse_required_attribute(&attributes, "build")?,
+                            });
+                            None
+                        }
+                        b"size" => {
+                            package.size = Some(Size {
+                                package: parse_required_attribute(&attributes, "package")?,
+                                installed: parse_required_attribute(&attributes, "installed")?,
+                                archive: parse_required_attribute(&attributes, "archive")?,
+                            });
+                            None
+                        }
+                        b"location" => {
+                            package.location = Some(Location {
+                                href: parse_required_attribute(&attributes, "href")?,
+                            });
+                            None
+                        }
+                        b"header-range" => {
+                            package.format.header_range = Some(HeaderRange {
+                                start: parse_required_attribute(&attributes, "start")?,
+                                end: parse_required_attribute(&attributes, "end")?,
+                            });
+                            None
+                        }
+                        b"provides" | b"requires" | b"conflicts" | b"obsoletes" | b"recommends"
+                        | b"suggests" | b"supplements" | b"enhances" => {
+                            let kind = match name {
+                                b"provides" => EntriesKind::Provides,
+                                b"requires" => EntriesKind::Requires,
+                                b"conflicts" => EntriesKind::Conflicts,
+                                b"obsoletes" => EntriesKind::Obsoletes,
+                                b"recommends" => EntriesKind::Recommends,
+                                b"suggests" => EntriesKind::Suggests,
+                                b"supplements" => EntriesKind::Supplements,
+                                _ => EntriesKind::Enhances,
+                            };
+                            package.format.get_entries_mut(kind);
+                            if !is_empty {
+                                entries_kind = Some(kind);
+                            }
+                            None
+                        }
+                        b"entry" => {
+                            if let Some(kind) = entries_kind {
+                                let entry = RpmEntry {
+                                    name: parse_required_attribute(&attributes, "name")?,
+                                    flags: parse_attribute(&attributes, "flags")?,
+                                    epoch: parse_attribute(&attributes, "epoch")?,
+                                    ver: parse_attribute(&attributes, "ver")?,
+                                    rel: parse_attribute(&attributes, "rel")?,
+                                };
+                                package.format.get_entries_mut(kind).entries.push(entry);
+                            }
+                            None
+                        }
+                        _ => None,
+                    };
+                    if let Some(field) = field {
+                        text.clear();
+                        if is_empty {
+                            set_text_field(package, field, attributes, String::new());
+                        } else {
+                            text_field = Some((field, attributes));
+                        }
+                    }
+                }
+                Event::Text(ref content) if text_field.is_some() => {
+                    text.push_str(&String::from_utf8(content.unescaped()?.into_owned())?);
+                }
+                Event::CData(ref content) if text_field.is_some() => {
+                    text.push_str(&String::from_utf8(content.to_vec())?);
+                }
+                Event::End(ref element) => match element.local_name() {
+                    b"package" => {
+                        if package.is_some() {
+                            return Ok(package);
+                        }
+                    }
+                    b"provides" | b"requires" | b"conflicts" | b"obsoletes" | b"recommends"
+                    | b"suggests" | b"supplements" | b"enhances" => entries_kind = None,
+                    _ => {
+                        if let (Some(package), Some((field, attributes))) =
+                            (package.as_mut(), text_field.take())
+                        {
+                            set_text_field(package, field, attributes, std::mem::take(&mut text));
+                        }
+                    }
+                },
+                Event::Eof if !self.has_root => {
+                    return Err(anyhow!("The root element <metadata> not found"))
+                }
+                Event::Eof => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn set_text_field(
+    package: &mut Package,
+    field: TextField,
+    mut attributes: HashMap<String, String>,
+    text: String,
+) {
+    match field {
+        TextField::Name => package.name = text,
+        TextField::Arch => package.arch = text,
+        TextField::Checksum => {
+            package.checksum = Some(Checksum {
+                checksum_type: attributes.remove("type").unwrap_or_default(),
+                pkgid: attributes.remove("pkgid"),
+                value: text,
+            })
+        }
+        TextField::Summary => package.summary = Some(text),
+        TextField::Description => package.description = Some(text),
+        TextField::Url => package.url = Some(text),
+        TextField::License => package.format.license = Some(text),
+        TextField::Vendor => package.format.vendor = Some(text),
+        TextField::Group => package.format.group = Some(text),
+        TextField::Buildhost => package.format.buildhost = Some(text),
+        TextField::Sourcerpm => package.format.sourcerpm = Some(text),
+        TextField::File => package.format.files.push(FileEntry {
+            r#type: attributes.remove("type"),
+            path: text,
+        }),
+    }
 }
 
 impl Repo {
     pub fn from_str(primary_xml: &str) -> Result<Repo> {
-        let mut repo: Repo =
-            quick_xml::de::from_str(&primary_xml).with_context(|| "Failed to parse primary.xml")?;
-        for (index, package) in repo.packages.iter().enumerate() {
-            if let Some(ref provides) = package.format.provides {
-                for entry in &provides.entries {
-                    if let Some(ids) = repo.providers.get_mut(&entry.name) {
-                        ids.push(index);
-                    } else {
-                        repo.providers.insert(entry.name.clone(), vec![index]);
+        Repo::from_reader(primary_xml.as_bytes())
+    }
+
+    // Parse primary.xml from the reader package by package, so neither the whole document
+    // nor the whole tree of its elements is kept in memory.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Repo> {
+        let mut repo = Repo::with_origin("");
+        let mut parser = PrimaryParser::new(reader);
+        while let Some(package) = parser
+            .next_package()
+            .with_context(|| "Failed to parse primary.xml")?
+        {
+            repo.add_package(package);
+        }
+        Ok(repo)
+    }
+
+    fn with_origin(id: &str) -> Repo {
+        Repo {
+            origins: vec![RepoOrigin {
+                id: id.to_string(),
+                priority: DEFAULT_PRIORITY,
+                cost: DEFAULT_COST,
+                installed: false,
+                filelists_loader: None,
+                other_loader: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    // The packages installed on the system, read from the rpmdb under the installroot.
+    // The repo id is "@System", the same as dnf.
+    pub fn from_rpmdb(installroot: &Path) -> Result<Repo> {
+        let mut repo = Repo::with_origin(INSTALLED_REPO_ID);
+        repo.origins[0].installed = true;
+        for blob in read_rpmdb_headers(installroot)? {
+            let package = Package::from_header(&Header::from_blob(&blob)?)?;
+            // The public keys imported into the rpmdb are not packages.
+            if package.name != "gpg-pubkey" {
+                repo.add_package(package);
+            }
+        }
+        Ok(repo)
+    }
+
+    // The packages in the local .rpm files, e.g. those just built, which can be merged with
+    // the other repos. The repo id is "@commandline", the same as dnf.
+    pub fn from_rpm_files<P: AsRef<Path>>(paths: &[P]) -> Result<Repo> {
+        let mut repo = Repo::with_origin(COMMANDLINE_REPO_ID);
+        for path in paths {
+            let path = path.as_ref();
+            let rpm = read_rpm_file(path)?;
+            let mut package = Package::from_header(&rpm.header)
+                .with_context(|| format!("Failed to read the rpm file {:?}", path))?;
+            package.checksum = Some(Checksum {
+                checksum_type: "sha256".to_string(),
+                pkgid: Some("YES".to_string()),
+                value: rpm.sha256,
+            });
+            package.location = Some(Location {
+                href: path.display().to_string(),
+            });
+            if let Some(size) = &mut package.size {
+                size.package = rpm.size;
+            }
+            package.format.header_range = Some(HeaderRange {
+                start: rpm.header_start,
+                end: rpm.header_end,
+            });
+            repo.add_package(package);
+        }
+        Ok(repo)
+    }
+
+    fn add_package(&mut self, package: Package) {
+        let index = self.packages.len();
+        if let Some(ref provides) = package.format.provides {
+            for entry in &provides.entries {
+                if let Some(ids) = self.providers.get_mut(&entry.name) {
+                    ids.push(index);
+                } else {
+                    self.providers.insert(entry.name.clone(), vec![index]);
+                }
+            }
+        }
+        for file in &package.format.files {
+            let ids = self.providers.entry(file.path.clone()).or_default();
+            if !ids.contains(&index) {
+                ids.push(index);
+            }
+        }
+        self.packages.push(package);
+    }
+
+    // Drop the packages matching any of the excludes, and, if includepkgs is not empty, those
+    // matching none of it, like the exclude and includepkgs options of dnf. The patterns are
+    // globs matched against the name, name.arch, name-version, name-version-release and NEVRA.
+    pub fn filter_packages(&mut self, excludes: &[String], includepkgs: &[String]) {
+        if excludes.is_empty() && includepkgs.is_empty() {
+            return;
+        }
+        let packages = std::mem::take(&mut self.packages);
+        self.providers.clear();
+        for package in packages {
+            let excluded = excludes
+                .iter()
+                .any(|pattern| package.matches_pattern(pattern));
+            let included = includepkgs.is_empty()
+                || includepkgs
+                    .iter()
+                    .any(|pattern| package.matches_pattern(pattern));
+            if included && !excluded {
+                self.add_package(package);
+            }
+        }
+        self.file_providers = OnceLock::new();
+        self.changelogs_loaded = OnceLock::new();
+    }
+
+    // Merge the packages of another repo into this one, so the dependencies can be satisfied
+    // across the repos, e.g. OS, update and EPOL. The package ids of the other repo are shifted
+    // by the number of the packages in this repo.
+    pub fn merge(&mut self, other: Repo) {
+        let origin_offset = self.origins.len();
+        self.origins.extend(other.origins);
+        for mut package in other.packages {
+            package.origin += origin_offset;
+            self.add_package(package);
+        }
+        self.file_providers = OnceLock::new();
+        self.changelogs_loaded = OnceLock::new();
+    }
+
+    // Set how to get filelists.xml, which is loaded at most once, and only when a file
+    // dependency is not resolved by the files in primary.xml.
+    // Like the priority, it is set for the repos merged into this one so far.
+    pub fn set_filelists_loader(
+        &mut self,
+        loader: impl Fn() -> Result<String> + Send + Sync + 'static,
+    ) {
+        let loader = Arc::new(loader);
+        for origin in &mut self.origins {
+            let loader = loader.clone();
+            origin.filelists_loader = Some(MetadataLoader::new(move || loader()));
+        }
+        self.file_providers = OnceLock::new();
+    }
+
+    fn get_file_providers(&self) -> &Result<HashMap<String, Vec<IdT>>> {
+        self.file_providers.get_or_init(|| {
+            let mut file_providers: HashMap<String, Vec<IdT>> = HashMap::new();
+            for (index, origin) in self.origins.iter().enumerate() {
+                let filelists = match origin.filelists_loader.as_ref() {
+                    Some(loader) => Filelists::from_str(&loader.load()?)?,
+                    None => continue,
+                };
+                let matcher = PackageMatcher::new(&self.packages, index);
+                for package in &filelists.packages {
+                    let version = &package.version;
+                    let id = match matcher.find(
+                        &package.pkgid,
+                        &package.name,
+                        version.epoch,
+                        &version.ver,
+                        &version.rel,
+                        &package.arch,
+                    ) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    for file in &package.files {
+                        let ids = file_providers.entry(file.path.clone()).or_default();
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
                     }
                 }
             }
+            Ok(file_providers)
+        })
+    }
+
+    // Return the error of loading filelists.xml, if a file dependency needed it. The file
+    // dependencies are left unresolved then, so the solving result would be wrong.
+    pub fn check_file_providers(&self) -> Result<()> {
+        match self.file_providers.get() {
+            Some(Err(e)) => Err(anyhow!("file dependencies can not be resolved: {:#}", e)),
+            _ => Ok(()),
+        }
+    }
+
+    // Set how to get other.xml, which is loaded when the changelogs are requested.
+    pub fn set_other_loader(
+        &mut self,
+        loader: impl Fn() -> Result<String> + Send + Sync + 'static,
+    ) {
+        let loader = Arc::new(loader);
+        for origin in &mut self.origins {
+            let loader = loader.clone();
+            origin.other_loader = Some(MetadataLoader::new(move || loader()));
         }
-        Ok(repo)
+    }
+
+    // Load the changelogs of all the packages from other.xml, if they are not loaded yet.
+    // Packages not listed in other.xml get no changelogs, as well as the packages of the repos
+    // without other.xml, e.g. the installed ones.
+    pub fn load_changelogs(&self) -> Result<()> {
+        if self.changelogs_loaded.get().is_some() {
+            return Ok(());
+        }
+        for (index, origin) in self.origins.iter().enumerate() {
+            let loader = match origin.other_loader.as_ref() {
+                Some(loader) => loader,
+                None => continue,
+            };
+            let otherdata = Otherdata::from_str(&loader.load()?)?;
+            let matcher = PackageMatcher::new(&self.packages, index);
+            for package in otherdata.packages {
+                let version = &package.version;
+                if let Some(id) = matcher.find(
+                    &package.pkgid,
+                    &package.name,
+                    version.epoch,
+                    &version.ver,
+                    &version.rel,
+                    &package.arch,
+                ) {
+                    let _ = self.packages[id].changelogs.set(package.changelogs);
+                }
+            }
+        }
+        for package in &self.packages {
+            let _ = package.changelogs.set(Vec::new());
+        }
+        let _ = self.changelogs_loaded.set(());
+        Ok(())
+    }
+
+    // Get the changelogs of the package, loading other.xml on demand.
+    pub fn get_package_changelogs(&self, package_id: IdT) -> Result<&Vec<Changelog>> {
+        self.load_changelogs()?;
+        self.packages
+            .get(package_id)
+            .and_then(|package| package.get_changelogs())
+            .ok_or_else(|| anyhow!("package {} not found in the repo", package_id))
     }
 
     pub fn from_baseurl(repo_baseurl: &str) -> Result<Repo> {
-        let repo_baseurl = if repo_baseurl.ends_with('/') {
-            repo_baseurl.to_string()
-        } else {
-            repo_baseurl.to_string() + "/"
-        };
-        let yum_variables = YumVariables::new()?;
-        let repo_baseurl = yum_variables.replace_yum_variables(repo_baseurl)?;
-        let primary_xml = Repomd::get_primary_xml(repo_baseurl)?;
-        Repo::from_str(&primary_xml)
+        Repo::from_baseurl_with_cache(repo_baseurl, None)
+    }
+
+    // Load the repo with its metadata cached on the disk. The cache is used by filelists.xml
+    // and other.xml as well, when they are loaded later.
+    pub fn from_baseurl_with_cache(
+        repo_baseurl: &str,
+        cache: Option<MetadataCache>,
+    ) -> Result<Repo> {
+        Repo::from_mirrors_with_cache(Mirrors::from_baseurl(repo_baseurl), cache)
+    }
+
+    // Load the repo from its mirrors, e.g. those in a mirrorlist or metalink, failing over
+    // to the next mirror when one of them fails.
+    pub fn from_mirrors_with_cache(mirrors: Mirrors, cache: Option<MetadataCache>) -> Result<Repo> {
+        // The metadata of local repos is read directly, without being cached.
+        let cache = cache.filter(|_| !mirrors.is_local());
+        // filelists.xml and other.xml are loaded later with the same repomd.xml as primary.xml,
+        // even if the repo has been updated since, so they list the same packages.
+        let repomd = Arc::new(Repomd::fetch(&mirrors, cache.as_ref())?);
+        // A corrupted primary.xml is reported as such, even if it fails to be parsed.
+        let mut repo = repomd.read_primary(&mirrors, cache.as_ref(), |primary_reader| {
+            Repo::from_reader(primary_reader)
+        })?;
+        let mirrors = Arc::new(mirrors);
+        let (filelists_repomd, filelists_mirrors, filelists_cache) =
+            (repomd.clone(), mirrors.clone(), cache.clone());
+        repo.set_filelists_loader(move || {
+            filelists_repomd.get_filelists_xml(&filelists_mirrors, filelists_cache.as_ref())
+        });
+        repo.set_other_loader(move || repomd.get_other_xml(&mirrors, cache.as_ref()));
+        Ok(repo)
     }
 
     pub fn get_package_id_by_name(&self, name: &str) -> Option<IdT> {
@@ -92,6 +782,87 @@ impl Repo {
         None
     }
 
+    // Set the priority of the repos merged into this one so far, so set it before merging.
+    pub fn set_priority(&mut self, priority: i32) {
+        for origin in &mut self.origins {
+            origin.priority = priority;
+        }
+    }
+
+    // Set the cost of the repos merged into this one so far, like the priority.
+    pub fn set_cost(&mut self, cost: i32) {
+        for origin in &mut self.origins {
+            origin.cost = cost;
+        }
+    }
+
+    pub fn get_package_cost(&self, package_id: IdT) -> i32 {
+        self.packages
+            .get(package_id)
+            .map_or(DEFAULT_COST, |package| self.origins[package.origin].cost)
+    }
+
+    pub fn get_package_priority(&self, package_id: IdT) -> i32 {
+        self.packages
+            .get(package_id)
+            .map_or(DEFAULT_PRIORITY, |package| {
+                self.origins[package.origin].priority
+            })
+    }
+
+    // Set the id of the repos merged into this one so far, e.g. the name of the repo in the config.
+    pub fn set_repo_id(&mut self, repo_id: &str) {
+        for origin in &mut self.origins {
+            origin.id = repo_id.to_string();
+        }
+    }
+
+    pub fn is_package_installed(&self, package_id: IdT) -> bool {
+        self.packages
+            .get(package_id)
+            .is_some_and(|package| self.origins[package.origin].installed)
+    }
+
+    // The ids of the installed packages, in ascending order.
+    pub fn get_installed_package_ids(&self) -> Vec<IdT> {
+        (0..self.packages.len())
+            .filter(|&id| self.is_package_installed(id))
+            .collect()
+    }
+
+    // The id of the repo the package comes from.
+    pub fn get_package_repo_id(&self, package_id: IdT) -> Option<&str> {
+        self.packages
+            .get(package_id)
+            .map(|package| self.origins[package.origin].id.as_str())
+    }
+
+    pub fn get_package_ids_by_name(&self, name: &str) -> Vec<IdT> {
+        self.packages
+            .iter()
+            .enumerate()
+            .filter(|(_, package)| package.name == name)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    pub fn get_package_count(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn get_package_by_id(&self, package_id: IdT) -> Option<&Package> {
+        self.packages.get(package_id)
+    }
+
+    pub fn get_package_provides_by_id(&self, package_id: IdT) -> Option<&Vec<RpmEntry>> {
+        if let Some(package) = self.packages.get(package_id) {
+            if let Some(ref e) = package.format.provides {
+                return Some(&e.entries);
+            }
+        }
+        None
+    }
+
     pub fn get_package_requires_by_id<'a>(&'a self, package_id: IdT) -> Option<&'a Vec<RpmEntry>> {
         if let Some(package) = self.packages.get(package_id) {
             if let Some(ref e) = package.format.requires {
@@ -119,8 +890,50 @@ impl Repo {
         None
     }
 
+    pub fn get_package_recommends_by_id(&self, package_id: IdT) -> Option<&Vec<RpmEntry>> {
+        if let Some(package) = self.packages.get(package_id) {
+            if let Some(ref e) = package.format.recommends {
+                return Some(&e.entries);
+            }
+        }
+        None
+    }
+
+    pub fn get_package_suggests_by_id(&self, package_id: IdT) -> Option<&Vec<RpmEntry>> {
+        if let Some(package) = self.packages.get(package_id) {
+            if let Some(ref e) = package.format.suggests {
+                return Some(&e.entries);
+            }
+        }
+        None
+    }
+
+    pub fn get_package_supplements_by_id(&self, package_id: IdT) -> Option<&Vec<RpmEntry>> {
+        if let Some(package) = self.packages.get(package_id) {
+            if let Some(ref e) = package.format.supplements {
+                return Some(&e.entries);
+            }
+        }
+        None
+    }
+
+    pub fn get_package_enhances_by_id(&self, package_id: IdT) -> Option<&Vec<RpmEntry>> {
+        if let Some(package) = self.packages.get(package_id) {
+            if let Some(ref e) = package.format.enhances {
+                return Some(&e.entries);
+            }
+        }
+        None
+    }
+
     pub fn get_entry_provider_id(&self, entry: &RpmEntry) -> Option<&Vec<IdT>> {
-        self.providers.get(&entry.name)
+        match self.providers.get(&entry.name) {
+            None if entry.name.starts_with('/') => match self.get_file_providers() {
+                Ok(file_providers) => file_providers.get(&entry.name),
+                Err(_) => None,
+            },
+            providers => providers,
+        }
     }
 
     fn get_entry_by_provider_id(&self, provider_id: IdT, entry_name: &str) -> Option<&RpmEntry> {
@@ -220,7 +1033,311 @@ impl Repo {
     }
 }
 
+// The dependencies of the given kind in the rpm header, e.g. the requires.
+fn get_header_entries(
+    header: &Header,
+    name_tag: u32,
+    flags_tag: u32,
+    version_tag: u32,
+) -> Result<Option<Entries>> {
+    let names = header.get_string_array(name_tag)?;
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let flags = header.get_integers(flags_tag)?;
+    let versions = header.get_string_array(version_tag)?;
+    let mut entries = Vec::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let flags = flags.get(i).copied().unwrap_or(0) as u32;
+        // The rpmlib() dependencies are provided by rpm itself, createrepo drops them as well.
+        if flags & RPMSENSE_RPMLIB != 0 || name.starts_with("rpmlib(") {
+            continue;
+        }
+        let version = versions.get(i).map(String::as_str).unwrap_or_default();
+        let op = flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL);
+        let entry = if op == 0 || version.is_empty() {
+            RpmEntry {
+                name,
+                flags: None,
+                epoch: None,
+                ver: None,
+                rel: None,
+            }
+        } else {
+            let flags = match op {
+                RPMSENSE_LESS => "LT",
+                RPMSENSE_GREATER => "GT",
+                RPMSENSE_EQUAL => "EQ",
+                op if op == RPMSENSE_LESS | RPMSENSE_EQUAL => "LE",
+                op if op == RPMSENSE_GREATER | RPMSENSE_EQUAL => "GE",
+                _ => continue,
+            };
+            let (epoch, ver, rel) = parse_evr(version);
+            RpmEntry {
+                name,
+                flags: Some(flags.to_string()),
+                epoch: Some(epoch),
+                ver: Some(ver.to_string()),
+                rel: rel.map(|rel| rel.to_string()),
+            }
+        };
+        entries.push(entry);
+    }
+    Ok((!entries.is_empty()).then_some(Entries { entries }))
+}
+
+// Split [epoch:]version[-release], the epoch is 0 if it is omitted.
+pub(crate) fn parse_evr(evr: &str) -> (i32, &str, Option<&str>) {
+    let (epoch, version_release) = match evr.split_once(':') {
+        Some((epoch, version_release)) => (epoch.parse().unwrap_or(0), version_release),
+        None => (0, evr),
+    };
+    match version_release.rsplit_once('-') {
+        Some((ver, rel)) => (epoch, ver, Some(rel)),
+        None => (epoch, version_release, None),
+    }
+}
+
 impl Package {
+    // Build the package from its rpm header, as stored in the rpmdb or an .rpm file.
+    pub(crate) fn from_header(header: &Header) -> Result<Package> {
+        let get_string = |tag| -> Result<String> {
+            header
+                .get_string(tag)?
+                .ok_or_else(|| anyhow!("tag {} not found in the rpm header", tag))
+        };
+        let name = get_string(RPMTAG_NAME)?;
+        // The files are stored as the indexes of their directories and their base names.
+        let dirnames = header.get_string_array(RPMTAG_DIRNAMES)?;
+        let dirindexes = header.get_integers(RPMTAG_DIRINDEXES)?;
+        let filemodes = header.get_integers(RPMTAG_FILEMODES)?;
+        let mut files = Vec::new();
+        for (i, basename) in header
+            .get_string_array(RPMTAG_BASENAMES)?
+            .into_iter()
+            .enumerate()
+        {
+            let dirname = dirindexes
+                .get(i)
+                .and_then(|&index| dirnames.get(index as usize))
+                .ok_or_else(|| anyhow!("invalid directory of file {} in {}", basename, name))?;
+            let is_dir = filemodes
+                .get(i)
+                .is_some_and(|mode| mode & 0o170000 == 0o040000);
+            files.push(FileEntry {
+                r#type: is_dir.then(|| "dir".to_string()),
+                path: format!("{}{}", dirname, basename),
+            });
+        }
+        let size = header
+            .get_integer(RPMTAG_LONGSIZE)?
+            .or(header.get_integer(RPMTAG_SIZE)?);
+        Ok(Package {
+            name,
+            arch: header
+                .get_string(RPMTAG_ARCH)?
+                .unwrap_or_else(|| "noarch".to_string()),
+            version: Version {
+                epoch: header.get_integer(RPMTAG_EPOCH)?.unwrap_or(0) as i32,
+                ver: get_string(RPMTAG_VERSION)?,
+                rel: get_string(RPMTAG_RELEASE)?,
+            },
+            summary: header.get_string(RPMTAG_SUMMARY)?,
+            description: header.get_string(RPMTAG_DESCRIPTION)?,
+            url: header.get_string(RPMTAG_URL)?,
+            time: header
+                .get_integer(RPMTAG_BUILDTIME)?
+                .map(|build| Time { file: build, build }),
+            size: size.map(|installed| Size {
+                package: 0,
+                installed,
+                archive: 0,
+            }),
+            format: Format {
+                license: header.get_string(RPMTAG_LICENSE)?,
+                vendor: header.get_string(RPMTAG_VENDOR)?,
+                group: header.get_string(RPMTAG_GROUP)?,
+                buildhost: header.get_string(RPMTAG_BUILDHOST)?,
+                sourcerpm: header.get_string(RPMTAG_SOURCERPM)?,
+                provides: get_header_entries(
+                    header,
+                    RPMTAG_PROVIDENAME,
+                    RPMTAG_PROVIDEFLAGS,
+                    RPMTAG_PROVIDEVERSION,
+                )?,
+                requires: get_header_entries(
+                    header,
+                    RPMTAG_REQUIRENAME,
+                    RPMTAG_REQUIREFLAGS,
+                    RPMTAG_REQUIREVERSION,
+                )?,
+                conflicts: get_header_entries(
+                    header,
+                    RPMTAG_CONFLICTNAME,
+                    RPMTAG_CONFLICTFLAGS,
+                    RPMTAG_CONFLICTVERSION,
+                )?,
+                obsoletes: get_header_entries(
+                    header,
+                    RPMTAG_OBSOLETENAME,
+                    RPMTAG_OBSOLETEFLAGS,
+                    RPMTAG_OBSOLETEVERSION,
+                )?,
+                recommends: get_header_entries(
+                    header,
+                    RPMTAG_RECOMMENDNAME,
+                    RPMTAG_RECOMMENDFLAGS,
+                    RPMTAG_RECOMMENDVERSION,
+                )?,
+                suggests: get_header_entries(
+                    header,
+                    RPMTAG_SUGGESTNAME,
+                    RPMTAG_SUGGESTFLAGS,
+                    RPMTAG_SUGGESTVERSION,
+                )?,
+                supplements: get_header_entries(
+                    header,
+                    RPMTAG_SUPPLEMENTNAME,
+                    RPMTAG_SUPPLEMENTFLAGS,
+                    RPMTAG_SUPPLEMENTVERSION,
+                )?,
+                enhances: get_header_entries(
+                    header,
+                    RPMTAG_ENHANCENAME,
+                    RPMTAG_ENHANCEFLAGS,
+                    RPMTAG_ENHANCEVERSION,
+                )?,
+                files,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_arch(&self) -> &String {
+        &self.arch
+    }
+
+    pub fn get_epoch(&self) -> i32 {
+        self.version.epoch
+    }
+
+    pub fn get_ver(&self) -> &String {
+        &self.version.ver
+    }
+
+    pub fn get_rel(&self) -> &String {
+        &self.version.rel
+    }
+
+    pub fn get_checksum(&self) -> Option<&Checksum> {
+        self.checksum.as_ref()
+    }
+
+    pub fn get_summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn get_time(&self) -> Option<Time> {
+        self.time
+    }
+
+    pub fn get_size(&self) -> Option<Size> {
+        self.size
+    }
+
+    // The path of the package file, relative to the baseurl of the repo.
+    pub fn get_location_href(&self) -> Option<&str> {
+        self.location
+            .as_ref()
+            .map(|location| location.href.as_str())
+    }
+
+    pub fn get_license(&self) -> Option<&str> {
+        self.format.license.as_deref()
+    }
+
+    pub fn get_vendor(&self) -> Option<&str> {
+        self.format.vendor.as_deref()
+    }
+
+    pub fn get_group(&self) -> Option<&str> {
+        self.format.group.as_deref()
+    }
+
+    pub fn get_buildhost(&self) -> Option<&str> {
+        self.format.buildhost.as_deref()
+    }
+
+    pub fn get_sourcerpm(&self) -> Option<&str> {
+        self.format.sourcerpm.as_deref()
+    }
+
+    pub fn get_header_range(&self) -> Option<HeaderRange> {
+        self.format.header_range
+    }
+
+    // None until the changelogs of the repo are loaded.
+    pub fn get_changelogs(&self) -> Option<&Vec<Changelog>> {
+        self.changelogs.get()
+    }
+
+    // The EVR of the package as an entry, to be compared by the functions in version.rs.
+    pub(crate) fn get_evr_entry(&self) -> RpmEntry {
+        RpmEntry {
+            name: self.name.clone(),
+            flags: Some("EQ".to_string()),
+            epoch: Some(self.version.epoch),
+            ver: Some(self.version.ver.clone()),
+            rel: Some(self.version.rel.clone()),
+        }
+    }
+
+    // NEVRA is formatted as name-[epoch:]version-release.arch, the epoch is omitted when it is 0.
+    pub fn get_nevra(&self) -> String {
+        Package::format_nevra(
+            &self.name,
+            self.version.epoch,
+            &self.version.ver,
+            &self.version.rel,
+            &self.arch,
+        )
+    }
+
+    fn matches_pattern(&self, pattern: &str) -> bool {
+        let name_ver = format!("{}-{}", self.name, self.version.ver);
+        let name_ver_rel = format!("{}-{}", name_ver, self.version.rel);
+        [
+            self.name.clone(),
+            format!("{}.{}", self.name, self.arch),
+            name_ver,
+            name_ver_rel,
+            self.get_nevra(),
+        ]
+        .iter()
+        .any(|text| glob_match(pattern.as_bytes(), text.as_bytes()))
+    }
+
+    pub(crate) fn format_nevra(name: &str, epoch: i32, ver: &str, rel: &str, arch: &str) -> String {
+        if epoch == 0 {
+            format!("{}-{}-{}.{}", name, ver, rel, arch)
+        } else {
+            format!("{}-{}:{}-{}.{}", name, epoch, ver, rel, arch)
+        }
+    }
+
     pub fn requires(self) -> Option<Vec<RpmEntry>> {
         if let Some(e) = self.format.requires {
             Some(e.entries)
@@ -276,10 +1393,125 @@ impl RpmEntry {
     }
 }
 
+// Format the entry like rpm does, e.g. "glibc >= 2.34-1".
+impl fmt::Display for RpmEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(flags) = &self.flags {
+            let op = match flags.as_str() {
+                "LT" => "<",
+                "LE" => "<=",
+                "EQ" => "=",
+                "GE" => ">=",
+                "GT" => ">",
+                _ => return Ok(()),
+            };
+            write!(f, " {} ", op)?;
+            if let Some(epoch) = self.epoch {
+                if epoch != 0 {
+                    write!(f, "{}:", epoch)?;
+                }
+            }
+            if let Some(ver) = &self.ver {
+                write!(f, "{}", ver)?;
+            }
+            if let Some(rel) = &self.rel {
+                write!(f, "-{}", rel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Match the text with a shell glob pattern of *, ? and [...], as used by the exclude option of dnf.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((b'[', rest)) => match (rest.iter().position(|&c| c == b']'), text.first()) {
+            (Some(end), Some(&c)) => {
+                let (set, negated) = match &rest[..end] {
+                    [b'!' | b'^', set @ ..] => (set, true),
+                    set => (set, false),
+                };
+                let mut matched = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if i + 2 < set.len() && set[i + 1] == b'-' {
+                        matched |= set[i] <= c && c <= set[i + 2];
+                        i += 3;
+                    } else {
+                        matched |= set[i] == c;
+                        i += 1;
+                    }
+                }
+                matched != negated && glob_match(&rest[end + 1..], &text[1..])
+            }
+            _ => false,
+        },
+        Some((&c, rest)) => text.first() == Some(&c) && glob_match(rest, &text[1..]),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_repo_is_send_and_sync() {
+        // The repo is shared by the threads checking its packages in parallel.
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Repo>();
+    }
+
+    #[test]
+    fn test_glob_match() {
+        for (pattern, text, expected) in [
+            ("kernel*", "kernel-devel", true),
+            ("kernel*", "perl-kernel", false),
+            ("*-devel", "glibc-devel", true),
+            ("python3-?", "python3-a", true),
+            ("python3-?", "python3-", false),
+            ("lib[a-c]x", "libbx", true),
+            ("lib[!a-c]x", "libbx", false),
+            ("lib[!a-c]x", "libdx", true),
+            ("bash", "bash", true),
+            ("bash", "bash-completion", false),
+        ] {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), text.as_bytes()),
+                expected,
+                "{} {}",
+                pattern,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_str_invalid() {
+        assert!(Repo::from_str("garbage").is_err());
+        assert!(Repo::from_str("").is_err());
+        assert!(Repo::from_str("<filelists><package/></filelists>").is_err());
+        assert!(Repo::from_str("<metadata packages=\"0\"/>").is_ok());
+    }
+
+    #[test]
+    fn test_entries_end() -> Result<()> {
+        // An entry after the end of the weak dependencies does not belong to them.
+        let repo = Repo::from_str(
+            r#"<metadata xmlns:rpm="http://linux.duke.edu/metadata/rpm" packages="1">
+<package type="rpm"><name>A</name><arch>noarch</arch><version epoch="0" ver="1" rel="1"/>
+<format><rpm:recommends><rpm:entry name="B"/></rpm:recommends><rpm:entry name="C"/></format>
+</package></metadata>"#,
+        )?;
+        let recommends = repo.get_package_recommends_by_id(0).unwrap();
+        assert_eq!(recommends.len(), 1);
+        assert_eq!(recommends[0].name, "B");
+        Ok(())
+    }
+
     #[test]
     fn test_parse_primary_xml() -> Result<()> {
         let repo_url = String::from("https://repo.openeuler.org/openEuler-22.03-LTS/OS/x86_64/");
@@ -0,0 +1,77 @@
+use crate::problem::Problem;
+use crate::repo::Repo;
+use crate::solve::{check_package_in_repo, RuleCache, SolveOptions};
+use anyhow::Result;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RepoclosureReport {
+    // Number of the checked packages.
+    pub checked: usize,
+    // NEVRAs of the packages which can not be installed.
+    pub broken: Vec<String>,
+    // The broken packages grouped by the problems which make them uninstallable.
+    pub reasons: BTreeMap<String, Vec<String>>,
+}
+
+impl RepoclosureReport {
+    fn add_broken_package(&mut self, nevra: String, problems: &[Problem]) {
+        // Group by the root causes only, as the requires chains leading to them
+        // are different for every broken package.
+        let root_causes: Vec<&Problem> = problems
+            .iter()
+            .filter(|problem| !matches!(problem, Problem::RequiresUninstallable { .. }))
+            .collect();
+        let reasons = if root_causes.is_empty() {
+            problems.iter().collect()
+        } else {
+            root_causes
+        };
+        for problem in reasons {
+            self.reasons
+                .entry(problem.to_string())
+                .or_default()
+                .push(nevra.clone());
+        }
+        self.broken.push(nevra);
+    }
+}
+
+// Check whether every package in the repo can be installed, like the repoclosure command of dnf.
+// The rules of each package are built once and reused by the checks of all the packages.
+pub fn check_repo_closure(repo: &Repo, options: &SolveOptions) -> Result<RepoclosureReport> {
+    let cache = RuleCache::new(repo);
+    let mut report = RepoclosureReport::default();
+    for package_id in 0..repo.get_package_count() {
+        if let Some(problems) = check_package_in_repo(&cache, options, package_id)? {
+            let nevra = repo.get_package_by_id(package_id).unwrap().get_nevra();
+            report.add_broken_package(nevra, &problems);
+        }
+        report.checked += 1;
+    }
+    Ok(report)
+}
+
+// The same as check_repo_closure, but the packages are checked by the threads of the rayon pool.
+// The results are collected in the order of the package ids, so the report does not depend on
+// the scheduling of the threads.
+pub fn check_repo_closure_parallel(
+    repo: &Repo,
+    options: &SolveOptions,
+) -> Result<RepoclosureReport> {
+    let cache = RuleCache::new(repo);
+    let results: Vec<Result<Option<Vec<Problem>>>> = (0..repo.get_package_count())
+        .into_par_iter()
+        .map(|package_id| check_package_in_repo(&cache, options, package_id))
+        .collect();
+    let mut report = RepoclosureReport::default();
+    for (package_id, result) in results.into_iter().enumerate() {
+        if let Some(problems) = result? {
+            let nevra = repo.get_package_by_id(package_id).unwrap().get_nevra();
+            report.add_broken_package(nevra, &problems);
+        }
+        report.checked += 1;
+    }
+    Ok(report)
+}
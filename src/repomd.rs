@@ -1,11 +1,16 @@
-use std::io::Read;
+use std::io::{self, BufRead, Read};
 
-use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
+use crate::cache::MetadataCache;
+use crate::checksum::{ChecksumReader, ChecksumType};
+use crate::compression::{get_decompressed_reader, Decoder};
+use crate::mirror::Mirrors;
+use anyhow::{anyhow, Context, Result};
 use serde::{self, Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Repomd {
+    revision: Option<String>,
     #[serde(rename = "data")]
     datas: Vec<Data>,
 }
@@ -13,40 +18,345 @@ pub struct Repomd {
 #[derive(Debug, Serialize, Deserialize)]
 struct Data {
     r#type: String,
+    timestamp: Option<u64>,
+    // The checksum of the compressed file.
+    checksum: Option<DataChecksum>,
+    // The checksum of the decompressed content.
+    #[serde(rename = "open-checksum")]
+    open_checksum: Option<DataChecksum>,
     location: Location,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct DataChecksum {
+    r#type: String,
+    #[serde(rename = "$value")]
+    value: String,
+}
+
+impl DataChecksum {
+    fn get_expected(&self) -> Result<(ChecksumType, String)> {
+        Ok((self.r#type.parse()?, self.value.trim().to_string()))
+    }
+}
+
+fn get_expected(checksum: &Option<DataChecksum>) -> Result<Option<(ChecksumType, String)>> {
+    checksum
+        .as_ref()
+        .map(DataChecksum::get_expected)
+        .transpose()
+}
+
+type CompressedReader = ChecksumReader<Box<dyn BufRead>>;
+
+// The decompressed content of a metadata file, whose checksum and open-checksum are computed
+// while it is read, and compared by verify.
+pub struct MetadataReader {
+    reader: ChecksumReader<Decoder<CompressedReader>>,
+}
+
+impl MetadataReader {
+    // Read the rest of the data and verify both of the checksums.
+    pub fn verify(&mut self) -> Result<()> {
+        let decompressed = io::copy(&mut self.reader, &mut io::sink());
+        // The compressed data may go on after the end of the decompressed stream. And a broken
+        // compressed file is reported as a checksum mismatch, rather than a decoding error.
+        self.reader.get_mut().get_mut().verify()?;
+        decompressed?;
+        self.reader.verify()
+    }
+}
+
+impl Read for MetadataReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for MetadataReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+impl Data {
+    fn get_compressed_reader<R: BufRead + 'static>(&self, reader: R) -> Result<CompressedReader> {
+        let href = &self.location.href;
+        let reader: Box<dyn BufRead> = Box::new(reader);
+        Ok(ChecksumReader::new(
+            reader,
+            href,
+            get_expected(&self.checksum)?,
+        ))
+    }
+
+    fn decompress<R: BufRead + 'static>(&self, compressed: R) -> Result<MetadataReader> {
+        let href = &self.location.href;
+        let reader = get_decompressed_reader(href, self.get_compressed_reader(compressed)?)?;
+        Ok(MetadataReader {
+            reader: ChecksumReader::new(reader, href, get_expected(&self.open_checksum)?),
+        })
+    }
+
+    // Read the data with the given function and verify it, even if the function failed,
+    // to tell a corrupted file from a malformed one.
+    fn read<R: BufRead + 'static, T>(
+        &self,
+        compressed: R,
+        read: &mut impl FnMut(&mut MetadataReader) -> Result<T>,
+    ) -> Result<T> {
+        let mut reader = self.decompress(compressed)?;
+        let result = read(&mut reader);
+        reader.verify()?;
+        result
+    }
+
+    fn is_same(&self, other: &Data) -> bool {
+        self.r#type == other.r#type
+            && self.timestamp == other.timestamp
+            && self.checksum == other.checksum
+            && self.location.href == other.location.href
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Location {
     href: String,
 }
 
 impl Repomd {
-    pub fn get_primary_xml(repo_url: String) -> Result<String> {
-        // Get repomd.xml from the repo.
-        let repomd_url = repo_url.clone() + "repodata/repomd.xml";
-        let repomd_xml = reqwest::blocking::get(&repomd_url)
-            .with_context(|| format!("Failed to connect to {:?}", &repomd_url))?
-            .text()?;
+    fn from_str(repomd_xml: &str) -> Result<Repomd> {
         // Deserialize repomd.xml into a structure using serde.
-        let repomd: Repomd =
-            quick_xml::de::from_str(&repomd_xml).with_context(|| "Failed to parse repomd.xml")?;
-        // Get the url of primary.xml.gz, download and decompress it.
-        let primary_data: Vec<Data> = repomd
+        quick_xml::de::from_str(repomd_xml).with_context(|| "Failed to parse repomd.xml")
+    }
+
+    // The metadata is not changed if the revision and all the data files are the same.
+    fn is_same(&self, other: &Repomd) -> bool {
+        self.revision == other.revision
+            && self.datas.len() == other.datas.len()
+            && self
+                .datas
+                .iter()
+                .zip(&other.datas)
+                .all(|(x, y)| x.is_same(y))
+    }
+
+    // Get repomd.xml from the cache if it is fresh, otherwise from the repo. All the data files
+    // of a repo have to be read with the same Repomd, so they are of the same revision.
+    pub fn fetch(mirrors: &Mirrors, cache: Option<&MetadataCache>) -> Result<Repomd> {
+        let cache = match cache {
+            Some(cache) => cache,
+            None => return Repomd::from_str(&String::from_utf8(mirrors.download_repomd()?)?),
+        };
+        let cached = cache
+            .read_repomd()
+            .and_then(|repomd_xml| Repomd::from_str(&repomd_xml).ok());
+        if let Some(cached) = cached {
+            if cache.is_offline() || cache.is_repomd_fresh() {
+                return Ok(cached);
+            }
+            let repomd_xml = String::from_utf8(mirrors.download_repomd()?)?;
+            let repomd = Repomd::from_str(&repomd_xml)?;
+            if repomd.is_same(&cached) {
+                cache.touch_repomd()?;
+                return Ok(cached);
+            }
+            Repomd::update_cache(cache, &repomd, &repomd_xml)?;
+            Ok(repomd)
+        } else if cache.is_offline() {
+            Err(anyhow!(
+                "repomd.xml of {} is not cached, and it can not be downloaded in offline mode",
+                mirrors
+            ))
+        } else {
+            let repomd_xml = String::from_utf8(mirrors.download_repomd()?)?;
+            let repomd = Repomd::from_str(&repomd_xml)?;
+            Repomd::update_cache(cache, &repomd, &repomd_xml)?;
+            Ok(repomd)
+        }
+    }
+
+    fn update_cache(cache: &MetadataCache, repomd: &Repomd, repomd_xml: &str) -> Result<()> {
+        cache.write_repomd(repomd_xml)?;
+        let hrefs: Vec<&str> = repomd
             .datas
-            .into_iter()
-            .filter(|data| data.r#type == "primary")
-            .collect();
-        let primary_gz_url = repo_url.clone() + &primary_data[0].location.href;
-        let primary_gz_bytes: Result<Vec<_>, _> = reqwest::blocking::get(&primary_gz_url)
-            .with_context(|| format!("Failed to connect to {:?}", &primary_gz_url))?
-            .bytes()?
-            .bytes()
+            .iter()
+            .map(|data| data.location.href.as_str())
             .collect();
-        let primary_gz_bytes = primary_gz_bytes.unwrap();
-        let mut primary_gz = GzDecoder::new(&primary_gz_bytes[..]);
-        let mut primary_xml = String::new();
-        primary_gz.read_to_string(&mut primary_xml)?;
-        Ok(primary_xml)
+        cache.remove_stale_data(&hrefs)
+    }
+
+    // Get the data file from the cache if its checksum matches repomd.xml, otherwise
+    // download it from the repo into the cache while verifying it.
+    fn read_cached_data<T>(
+        mirrors: &Mirrors,
+        data: &Data,
+        cache: &MetadataCache,
+        mut read: impl FnMut(&mut MetadataReader) -> Result<T>,
+    ) -> Result<T> {
+        let href = &data.location.href;
+        let is_cached = match cache.open_data(href) {
+            Some(file) => data.get_compressed_reader(file)?.verify().is_ok(),
+            None => false,
+        };
+        if !is_cached {
+            if cache.is_offline() {
+                return Err(anyhow!(
+                    "{} of {} is not cached, and it can not be downloaded in offline mode",
+                    href,
+                    mirrors
+                ));
+            }
+            mirrors.read(href, |reader| {
+                cache.write_data(href, &mut data.get_compressed_reader(reader)?)
+            })?;
+        }
+        let file = cache
+            .open_data(href)
+            .with_context(|| format!("Failed to read the cached {}", href))?;
+        data.read(file, &mut read)
+    }
+
+    // Read the data of the given type listed in repomd.xml, e.g. primary or filelists, with
+    // the given function, which gets it decompressed whatever the compression is. The data is
+    // streamed and verified while it is read, and a mirror it fails to be verified from fails
+    // over to the next one.
+    fn read_data<T>(
+        &self,
+        mirrors: &Mirrors,
+        data_type: &str,
+        cache: Option<&MetadataCache>,
+        mut read: impl FnMut(&mut MetadataReader) -> Result<T>,
+    ) -> Result<T> {
+        let data = self
+            .datas
+            .iter()
+            .find(|data| data.r#type == data_type)
+            .with_context(|| format!("{} not found in repomd.xml of {}", data_type, mirrors))?;
+        match cache {
+            Some(cache) => Repomd::read_cached_data(mirrors, data, cache, read),
+            None => mirrors.read(&data.location.href, |reader| data.read(reader, &mut read)),
+        }
+    }
+
+    fn get_data_xml(
+        &self,
+        mirrors: &Mirrors,
+        data_type: &str,
+        cache: Option<&MetadataCache>,
+    ) -> Result<String> {
+        self.read_data(mirrors, data_type, cache, |reader| {
+            let mut data_xml = String::new();
+            reader.read_to_string(&mut data_xml)?;
+            Ok(data_xml)
+        })
+    }
+
+    pub fn read_primary<T>(
+        &self,
+        mirrors: &Mirrors,
+        cache: Option<&MetadataCache>,
+        read: impl FnMut(&mut MetadataReader) -> Result<T>,
+    ) -> Result<T> {
+        self.read_data(mirrors, "primary", cache, read)
+    }
+
+    pub fn get_filelists_xml(
+        &self,
+        mirrors: &Mirrors,
+        cache: Option<&MetadataCache>,
+    ) -> Result<String> {
+        self.get_data_xml(mirrors, "filelists", cache)
+    }
+
+    pub fn get_other_xml(
+        &self,
+        mirrors: &Mirrors,
+        cache: Option<&MetadataCache>,
+    ) -> Result<String> {
+        self.get_data_xml(mirrors, "other", cache)
+    }
+}
+
+// Produces the content of a metadata file, e.g. filelists.xml or other.xml, which is only
+// downloaded and parsed when the data in it is needed.
+pub struct MetadataLoader(Box<dyn Fn() -> Result<String> + Send + Sync>);
+
+impl MetadataLoader {
+    pub fn new(loader: impl Fn() -> Result<String> + Send + Sync + 'static) -> MetadataLoader {
+        MetadataLoader(Box::new(loader))
+    }
+
+    pub fn load(&self) -> Result<String> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for MetadataLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MetadataLoader")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::ChecksumMismatch;
+    use flate2::write::GzEncoder;
+    use sha2::{Digest, Sha256};
+    use std::io::{Cursor, Write};
+
+    fn get_data(checksum: &str, open_checksum: &str) -> Result<Data> {
+        let repomd_xml = format!(
+            r#"<repomd xmlns="http://linux.duke.edu/metadata/repo">
+  <data type="primary">
+    <checksum type="sha256">{}</checksum>
+    <open-checksum type="sha256">{}</open-checksum>
+    <location href="repodata/primary.xml.gz"/>
+  </data>
+</repomd>"#,
+            checksum, open_checksum
+        );
+        let repomd: Repomd = quick_xml::de::from_str(&repomd_xml)?;
+        Ok(repomd.datas.into_iter().next().unwrap())
+    }
+
+    #[test]
+    fn test_verify_data() -> Result<()> {
+        let xml = b"<metadata packages=\"0\"></metadata>";
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml)?;
+        let compressed = encoder.finish()?;
+        let checksum = format!("{:x}", Sha256::digest(&compressed));
+        let open_checksum = format!("{:x}", Sha256::digest(xml));
+
+        let mut read_to_string = |reader: &mut MetadataReader| -> Result<String> {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            Ok(content)
+        };
+
+        let data = get_data(&checksum, &open_checksum)?;
+        let content = data.read(Cursor::new(compressed.clone()), &mut read_to_string)?;
+        assert_eq!(content.as_bytes(), xml);
+
+        // The checksum is verified even if the data failed to be read.
+        let error = get_data(&open_checksum, &open_checksum)?
+            .read(Cursor::new(compressed.clone()), &mut |_| {
+                Err::<(), _>(anyhow!("malformed"))
+            })
+            .unwrap_err();
+        let mismatch = error.downcast_ref::<ChecksumMismatch>().unwrap();
+        assert_eq!(mismatch.actual, checksum);
+
+        let mut reader = get_data(&checksum, &checksum)?.decompress(Cursor::new(compressed))?;
+        let error = reader.verify().unwrap_err();
+        let mismatch = error.downcast_ref::<ChecksumMismatch>().unwrap();
+        assert_eq!(mismatch.actual, open_checksum);
+        Ok(())
     }
 }
@@ -0,0 +1,257 @@
+use crate::repo::{parse_evr, RpmEntry};
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+// A rich (boolean) dependency of rpm >= 4.13, e.g. (python3-foo if python3).
+#[derive(Debug, Clone)]
+pub enum RichDep {
+    Simple(RpmEntry),
+    And(Vec<RichDep>),
+    Or(Vec<RichDep>),
+    // (A if B) or (A if B else C).
+    If(Box<RichDep>, Box<RichDep>, Option<Box<RichDep>>),
+    // (A unless B) or (A unless B else C).
+    Unless(Box<RichDep>, Box<RichDep>, Option<Box<RichDep>>),
+    // A single package matches all the operands.
+    With(Vec<RichDep>),
+    // A single package matches the first operand but not the second one.
+    Without(Box<RichDep>, Box<RichDep>),
+}
+
+// Rich dependencies are always enclosed in parentheses, which no plain capability starts with.
+pub fn is_rich_dep(name: &str) -> bool {
+    name.starts_with('(')
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(c) {
+            return Err(anyhow!("{:?} expected at {} of {:?}", c, self.pos, self.s));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // A word ends at a whitespace or an unbalanced ')', so names like perl(Foo::Bar) are kept whole.
+    fn read_word(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut depth = 0;
+        for c in self.s[start..].chars() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                c if c.is_whitespace() && depth == 0 => break,
+                _ => (),
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.s[start..self.pos]
+    }
+
+    fn peek_word(&mut self) -> &'a str {
+        let pos = self.pos;
+        let word = self.read_word();
+        self.pos = pos;
+        word
+    }
+
+    // name, or name op version.
+    fn parse_simple(&mut self) -> Result<RichDep> {
+        let name = self.read_word();
+        if name.is_empty() {
+            return Err(anyhow!(
+                "dependency expected at {} of {:?}",
+                self.pos,
+                self.s
+            ));
+        }
+        let flags = match self.peek_word() {
+            "<" => "LT",
+            "<=" => "LE",
+            "=" | "==" => "EQ",
+            ">=" => "GE",
+            ">" => "GT",
+            _ => {
+                return Ok(RichDep::Simple(RpmEntry {
+                    name: name.to_string(),
+                    flags: None,
+                    epoch: None,
+                    ver: None,
+                    rel: None,
+                }))
+            }
+        };
+        self.read_word();
+        let evr = self.read_word();
+        if evr.is_empty() {
+            return Err(anyhow!("version of {} expected in {:?}", name, self.s));
+        }
+        let (epoch, ver, rel) = parse_evr(evr);
+        Ok(RichDep::Simple(RpmEntry {
+            name: name.to_string(),
+            flags: Some(flags.to_string()),
+            epoch: Some(epoch),
+            ver: Some(ver.to_string()),
+            rel: rel.map(|rel| rel.to_string()),
+        }))
+    }
+
+    fn parse_operand(&mut self) -> Result<RichDep> {
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            self.parse_group()
+        } else {
+            self.parse_simple()
+        }
+    }
+
+    // The content of a parenthesized expression, after the '('. The same operator can be chained,
+    // e.g. (A and B and C), but different operators have to be nested.
+    fn parse_group(&mut self) -> Result<RichDep> {
+        let first = self.parse_operand()?;
+        self.skip_whitespace();
+        if self.peek() == Some(')') {
+            self.pos += 1;
+            return Ok(first);
+        }
+        let op = self.read_word();
+        let dep = match op {
+            "and" | "or" | "with" => {
+                let mut operands = vec![first, self.parse_operand()?];
+                while self.peek_word() == op {
+                    self.read_word();
+                    operands.push(self.parse_operand()?);
+                }
+                match op {
+                    "and" => RichDep::And(operands),
+                    "or" => RichDep::Or(operands),
+                    _ => RichDep::With(operands),
+                }
+            }
+            "if" | "unless" => {
+                let condition = Box::new(self.parse_operand()?);
+                let otherwise = if self.peek_word() == "else" {
+                    self.read_word();
+                    Some(Box::new(self.parse_operand()?))
+                } else {
+                    None
+                };
+                if op == "if" {
+                    RichDep::If(Box::new(first), condition, otherwise)
+                } else {
+                    RichDep::Unless(Box::new(first), condition, otherwise)
+                }
+            }
+            "without" => RichDep::Without(Box::new(first), Box::new(self.parse_operand()?)),
+            _ => return Err(anyhow!("unknown operator {:?} in {:?}", op, self.s)),
+        };
+        self.expect(')')?;
+        Ok(dep)
+    }
+}
+
+pub fn parse_rich_dep(s: &str) -> Result<RichDep> {
+    let mut parser = Parser { s, pos: 0 };
+    parser.expect('(')?;
+    let dep = parser.parse_group()?;
+    parser.skip_whitespace();
+    if parser.pos != s.len() {
+        return Err(anyhow!(
+            "unexpected {:?} after {:?}",
+            &s[parser.pos..],
+            &s[..parser.pos]
+        ));
+    }
+    Ok(dep)
+}
+
+impl fmt::Display for RichDep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, operands: &[&RichDep], op: &str| -> fmt::Result {
+            write!(f, "(")?;
+            for (i, operand) in operands.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                write!(f, "{}", operand)?;
+            }
+            write!(f, ")")
+        };
+        match self {
+            RichDep::Simple(entry) => write!(f, "{}", entry),
+            RichDep::And(operands) => join(f, &operands.iter().collect::<Vec<_>>(), "and"),
+            RichDep::Or(operands) => join(f, &operands.iter().collect::<Vec<_>>(), "or"),
+            RichDep::With(operands) => join(f, &operands.iter().collect::<Vec<_>>(), "with"),
+            RichDep::Without(x, y) => join(f, &[x, y], "without"),
+            RichDep::If(x, y, otherwise) | RichDep::Unless(x, y, otherwise) => {
+                let op = if matches!(self, RichDep::If(..)) {
+                    "if"
+                } else {
+                    "unless"
+                };
+                match otherwise {
+                    Some(z) => write!(f, "({} {} {} else {})", x, op, y, z),
+                    None => write!(f, "({} {} {})", x, op, y),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rich_dep() -> Result<()> {
+        for (s, expected) in [
+            ("(python3-foo if python3)", "(python3-foo if python3)"),
+            (
+                "(pkgA >= 1.0 with pkgA < 2.0)",
+                "(pkgA >= 1.0 with pkgA < 2.0)",
+            ),
+            ("(a and b and c)", "(a and b and c)"),
+            ("(a or (b and c))", "(a or (b and c))"),
+            ("(a if b else c)", "(a if b else c)"),
+            ("(a unless (b or c))", "(a unless (b or c))"),
+            ("(foo without foo-devel)", "(foo without foo-devel)"),
+            (
+                "(perl(Foo::Bar) or perl(Baz))",
+                "(perl(Foo::Bar) or perl(Baz))",
+            ),
+            ("( a = 1:2.0-3 )", "a = 1:2.0-3"),
+            ("((a))", "a"),
+        ] {
+            assert_eq!(parse_rich_dep(s)?.to_string(), expected);
+        }
+        for s in [
+            "(a and b or c)",
+            "(a and)",
+            "(a if b",
+            "(a xor b)",
+            "(a) b",
+            "a and b",
+        ] {
+            assert!(parse_rich_dep(s).is_err(), "{}", s);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+// The sqlite rpmdb used by rpm >= 4.16, relative to the installroot.
+const RPMDB_SQLITE_PATH: &str = "var/lib/rpm/rpmdb.sqlite";
+
+// Read the header blobs of all the installed packages from the rpmdb under the installroot.
+pub(crate) fn read_rpmdb_headers(installroot: &Path) -> Result<Vec<Vec<u8>>> {
+    let path = installroot.join(RPMDB_SQLITE_PATH);
+    if !path.exists() {
+        return Err(anyhow!(
+            "rpmdb {:?} not found, only the sqlite rpmdb is supported",
+            path
+        ));
+    }
+    // The rpmdb is only read, so it is never locked against rpm.
+    let connection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open the rpmdb {:?}", path))?;
+    let mut statement = connection.prepare("SELECT blob FROM Packages ORDER BY hnum")?;
+    let blobs = statement
+        .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+        .collect::<rusqlite::Result<Vec<Vec<u8>>>>()
+        .with_context(|| format!("Failed to read the packages in the rpmdb {:?}", path))?;
+    Ok(blobs)
+}
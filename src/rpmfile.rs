@@ -0,0 +1,78 @@
+use crate::header::{check_header_size, Header};
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+// The lead is the obsolete fixed size header at the beginning of every .rpm file.
+const LEAD_SIZE: usize = 96;
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+// The type of the package in the lead, 0 for binary packages and 1 for source packages.
+const LEAD_TYPE_SOURCE: u16 = 1;
+// Every header starts with the magic, the version 1 and 4 reserved bytes.
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+// The headers of an .rpm file, the payload is not read.
+pub(crate) struct RpmFile {
+    pub header: Header,
+    // The byte range of the main header in the file, as the header-range in primary.xml.
+    pub header_start: u64,
+    pub header_end: u64,
+    pub size: u64,
+    // The sha256 of the whole file, used as the pkgid by createrepo.
+    pub sha256: String,
+}
+
+// Read a header following its magic, and return the header and its size in the file.
+fn read_header<R: Read>(reader: &mut R, hasher: &mut Sha256) -> Result<(Header, usize)> {
+    let mut intro = [0u8; 16];
+    reader.read_exact(&mut intro)?;
+    hasher.update(intro);
+    if intro[..4] != HEADER_MAGIC {
+        return Err(anyhow!("bad magic of the rpm header"));
+    }
+    let index_count = u32::from_be_bytes([intro[8], intro[9], intro[10], intro[11]]) as usize;
+    let store_size = u32::from_be_bytes([intro[12], intro[13], intro[14], intro[15]]) as usize;
+    // The sizes are checked before the header is read, so a broken file fails without
+    // allocating whatever it declares.
+    check_header_size(index_count, store_size)?;
+    let mut blob = intro[8..].to_vec();
+    blob.resize(8 + index_count * 16 + store_size, 0);
+    reader.read_exact(&mut blob[8..])?;
+    hasher.update(&blob[8..]);
+    Ok((Header::from_blob(&blob)?, 8 + blob.len()))
+}
+
+// Read the lead, the signature header and the main header of the .rpm file.
+pub(crate) fn read_rpm_file(path: &Path) -> Result<RpmFile> {
+    let read = || -> Result<RpmFile> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hasher = Sha256::new();
+        let mut lead = [0u8; LEAD_SIZE];
+        reader.read_exact(&mut lead)?;
+        hasher.update(lead);
+        if lead[..4] != LEAD_MAGIC {
+            return Err(anyhow!("not an rpm file"));
+        }
+        if u16::from_be_bytes([lead[6], lead[7]]) == LEAD_TYPE_SOURCE {
+            return Err(anyhow!("source packages can not be installed"));
+        }
+        // The signature header is padded to a multiple of 8 bytes.
+        let (_, signature_size) = read_header(&mut reader, &mut hasher)?;
+        let mut padding = vec![0u8; (8 - signature_size % 8) % 8];
+        reader.read_exact(&mut padding)?;
+        hasher.update(&padding);
+        let header_start = LEAD_SIZE + signature_size + padding.len();
+        let (header, header_size) = read_header(&mut reader, &mut hasher)?;
+        let payload_size = io::copy(&mut reader, &mut hasher)?;
+        Ok(RpmFile {
+            header,
+            header_start: header_start as u64,
+            header_end: (header_start + header_size) as u64,
+            size: (header_start + header_size) as u64 + payload_size,
+            sha256: format!("{:x}", hasher.finalize()),
+        })
+    };
+    read().with_context(|| format!("Failed to read the rpm file {:?}", path))
+}
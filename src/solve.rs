@@ -1,7 +1,15 @@
-use crate::repo::{IdT, Repo};
+use crate::cardinality::build_totalizer;
+use crate::config::{Config, DEFAULT_INSTALLONLYPKGS};
+use crate::policy::{get_default_arches, sort_candidates};
+use crate::problem::Problem;
+use crate::repo::{IdT, Repo, RpmEntry};
+use crate::richdep::{is_rich_dep, parse_rich_dep, RichDep};
+use crate::version::evr_compare;
 use anyhow::{anyhow, Result};
-use std::collections::{HashSet, VecDeque};
-use varisat::{solver::Solver, CnfFormula, ExtendFormula, Lit};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::OnceLock;
+use varisat::{solver::Solver, ExtendFormula, Lit};
 
 pub enum ReturnValue {
     Satisfied,
@@ -10,100 +18,1293 @@ pub enum ReturnValue {
     PackageNotFound,
 }
 
-fn get_formula_by_package_id(repo: &Repo, package_id: IdT) -> Result<CnfFormula> {
-    let mut q = VecDeque::new();
-    let mut formula = CnfFormula::new();
-    let mut appeared = HashSet::new();
-    q.push_back(package_id);
-    appeared.insert(package_id);
-    while let Some(package_id) = q.pop_front() {
-        if let Some(requires) = repo.get_package_requires_by_id(package_id) {
-            for entry in requires {
-                if let Some(providers) = repo.get_entry_provider_id(entry) {
-                    let mut clause: Vec<Lit> = providers
+// A request of the user.
+#[derive(Debug, Clone)]
+pub enum Job {
+    // Install a package with the given name.
+    Install(String),
+    // Install the package with the given id, e.g. one read from a local .rpm file.
+    InstallPackage(IdT),
+}
+
+#[derive(Debug, Clone)]
+pub struct SolveOptions {
+    // Packages which can be installed in several versions at the same time,
+    // matched against the names and the provides of the packages.
+    pub installonlypkgs: Vec<String>,
+    // Architectures accepted by the host, the best first.
+    pub arches: Vec<String>,
+    // Install as few packages as possible, before applying the preferences.
+    pub minimize: bool,
+    // Install the packages recommended by or supplementing the packages to be installed if
+    // possible, the same as install_weak_deps of dnf.
+    pub install_weak_deps: bool,
+}
+
+// Why a package is pulled into the transaction.
+#[derive(Debug, Clone)]
+pub enum Reason {
+    // The package is requested by the user.
+    Job,
+    // The package provides the entry required by the package with the given id.
+    Requires(IdT, RpmEntry),
+    // The package upgrades the installed package with the given id.
+    Upgrade(IdT),
+    // The package replaces the installed package with the given id, which it obsoletes.
+    Obsoletes(IdT),
+    // The package provides the entry recommended by the package with the given id.
+    Recommends(IdT, RpmEntry),
+    // The package supplements the package with the given id.
+    Supplements(IdT),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionItem {
+    pub package_id: IdT,
+    pub nevra: String,
+    // The id of the repo the package is installed from.
+    pub repo_id: String,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Default)]
+pub struct Transaction {
+    items: Vec<TransactionItem>,
+}
+
+pub enum SolveResult {
+    Satisfied(Transaction),
+    Unsatisfied(Vec<Problem>),
+    // Some required capabilities are not provided by any package in the repo.
+    // Every problem is a Problem::NothingProvides.
+    MissingProviders(Vec<Problem>),
+    // No package with the given name is found in the repo.
+    PackageNotFound(String),
+}
+
+// Where a clause of the formula comes from.
+#[derive(Debug, Clone)]
+pub enum RuleOrigin<'a> {
+    // One of the packages is requested by the user.
+    Job(Vec<IdT>),
+    // The package requires the entry.
+    Requires(IdT, &'a RpmEntry),
+    // The package conflicts with the entry, which is provided by the other package.
+    Conflicts(IdT, &'a RpmEntry, IdT),
+    // The package conflicts with the rich dependency, which is one of the clauses of its negation.
+    RichConflicts(IdT, &'a RpmEntry),
+    // The package obsoletes the entry, which is provided by the other package.
+    Obsoletes(IdT, &'a RpmEntry, IdT),
+    // Only one of the two packages with the same name can be installed.
+    SameName(IdT, IdT),
+    // The installed package is kept, or upgraded or obsoleted by one of the other packages.
+    Installed(IdT),
+}
+
+#[derive(Debug, Clone)]
+struct Rule<'a> {
+    origin: RuleOrigin<'a>,
+    clause: Vec<Lit>,
+}
+
+// The rules of every package are built at most once, and shared by all the solves
+// over the same repo, e.g. when checking the closure of the whole repo.
+// The cache can be shared by several threads, each of them solving with its own solver.
+pub(crate) struct RuleCache<'a> {
+    repo: &'a Repo,
+    rules: Vec<OnceLock<Vec<Rule<'a>>>>,
+    // The packages supplementing every package, built on the first weak dependency.
+    supplementers: OnceLock<HashMap<IdT, Vec<IdT>>>,
+    // The packages obsoleting every installed package, built on the first installed package.
+    obsoleters: OnceLock<HashMap<IdT, Vec<IdT>>>,
+}
+
+// Every rule is guarded by a selector literal, which is assumed to be true when the rule is enabled.
+// So the failed assumptions of the solver can be mapped back to the rules causing the failure.
+struct RuleSolver<'a> {
+    solver: Solver<'a>,
+    selector_base: usize,
+    rule_count: usize,
+}
+
+impl Default for SolveOptions {
+    fn default() -> SolveOptions {
+        SolveOptions {
+            installonlypkgs: DEFAULT_INSTALLONLYPKGS
+                .iter()
+                .map(|pkg| pkg.to_string())
+                .collect(),
+            arches: get_default_arches(),
+            minimize: false,
+            install_weak_deps: true,
+        }
+    }
+}
+
+impl SolveOptions {
+    pub fn from_config(cfg: &Config) -> SolveOptions {
+        SolveOptions {
+            installonlypkgs: cfg.get_installonlypkgs(),
+            install_weak_deps: cfg.get_install_weak_deps(),
+            ..SolveOptions::default()
+        }
+    }
+
+    fn is_installonly(&self, repo: &Repo, package_id: IdT) -> bool {
+        let package = repo.get_package_by_id(package_id).unwrap();
+        if self.installonlypkgs.contains(package.get_name()) {
+            return true;
+        }
+        match repo.get_package_provides_by_id(package_id) {
+            Some(provides) => provides
+                .iter()
+                .any(|entry| self.installonlypkgs.contains(&entry.name)),
+            None => false,
+        }
+    }
+}
+
+impl Transaction {
+    pub fn get_items(&self) -> &Vec<TransactionItem> {
+        &self.items
+    }
+
+    pub fn get_package_ids(&self) -> Vec<IdT> {
+        self.items.iter().map(|item| item.package_id).collect()
+    }
+}
+
+fn is_true(model: &[Lit], package_id: IdT) -> bool {
+    model.get(package_id).is_some_and(|lit| lit.is_positive())
+}
+
+// The providers of the entry which satisfy its version constraint.
+fn get_providers(repo: &Repo, entry: &RpmEntry) -> Vec<IdT> {
+    match repo.get_entry_provider_id(entry) {
+        Some(providers) => providers
+            .iter()
+            .copied()
+            .filter(|id| repo.check_version_constraint(entry, id).unwrap_or(false))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// A formula over the package literals in negation normal form, built from a rich dependency.
+enum Formula {
+    // At least one of the literals is true, so an empty clause is false.
+    Clause(Vec<Lit>),
+    And(Vec<Formula>),
+    Or(Vec<Formula>),
+}
+
+impl Formula {
+    fn negate(self) -> Formula {
+        match self {
+            Formula::Clause(lits) => Formula::And(
+                lits.into_iter()
+                    .map(|lit| Formula::Clause(vec![!lit]))
+                    .collect(),
+            ),
+            Formula::And(formulas) => {
+                Formula::Or(formulas.into_iter().map(Formula::negate).collect())
+            }
+            Formula::Or(formulas) => {
+                Formula::And(formulas.into_iter().map(Formula::negate).collect())
+            }
+        }
+    }
+
+    // Convert the formula into CNF by distributing the disjunctions over the conjunctions.
+    // Rich dependencies are small, so the number of the clauses does not blow up in practice.
+    fn into_clauses(self) -> Vec<Vec<Lit>> {
+        match self {
+            Formula::Clause(lits) => vec![lits],
+            Formula::And(formulas) => formulas
+                .into_iter()
+                .flat_map(Formula::into_clauses)
+                .collect(),
+            Formula::Or(formulas) => {
+                let mut clauses = vec![Vec::new()];
+                for formula in formulas {
+                    let others = formula.into_clauses();
+                    clauses = clauses
                         .iter()
-                        .filter(|&id| {
-                            if let Ok(constraint) = repo.check_version_constraint(entry, id) {
-                                constraint
-                            } else {
-                                false
-                            }
+                        .flat_map(|clause: &Vec<Lit>| {
+                            others.iter().map(move |other| {
+                                let mut clause = clause.clone();
+                                for lit in other {
+                                    if !clause.contains(lit) {
+                                        clause.push(*lit);
+                                    }
+                                }
+                                clause
+                            })
                         })
-                        .map(|&id| Lit::from_index(id, true))
                         .collect();
-                    if clause.len() > 0 {
-                        for lit in &clause {
-                            if appeared.contains(&lit.index()) == false {
-                                appeared.insert(lit.index());
-                                q.push_back(lit.index());
-                            }
-                        }
-                        clause.push(Lit::from_index(package_id, false));
-                        formula.add_clause(&clause);
-                    } else {
-                        return Err(anyhow!("version constraint not satisfied"));
-                    }
                 }
+                // A clause containing both a literal and its negation is always true.
+                clauses.retain(|clause| !clause.iter().any(|lit| clause.contains(&!*lit)));
+                clauses
+            }
+        }
+    }
+}
+
+// The packages matching a plain entry, or a with or without expression, which are matched
+// by single packages. rpm accepts nothing else as the operands of with and without.
+fn get_rich_providers(repo: &Repo, dep: &RichDep) -> Vec<IdT> {
+    match dep {
+        RichDep::Simple(entry) => get_providers(repo, entry),
+        RichDep::With(operands) => {
+            let mut providers = get_rich_providers(repo, &operands[0]);
+            for operand in &operands[1..] {
+                let others = get_rich_providers(repo, operand);
+                providers.retain(|id| others.contains(id));
+            }
+            providers
+        }
+        RichDep::Without(x, y) => {
+            let mut providers = get_rich_providers(repo, x);
+            let others = get_rich_providers(repo, y);
+            providers.retain(|id| !others.contains(id));
+            providers
+        }
+        _ => Vec::new(),
+    }
+}
+
+// In the reverse dependencies, i.e. conflicts and supplements, if and unless are evaluated as
+// conditions on the matched packages as rpm does, so (A if B) is A and B.
+fn get_rich_formula(repo: &Repo, dep: &RichDep, reverse: bool) -> Formula {
+    let formula = |dep: &RichDep| get_rich_formula(repo, dep, reverse);
+    match dep {
+        RichDep::Simple(_) | RichDep::With(_) | RichDep::Without(..) => Formula::Clause(
+            get_rich_providers(repo, dep)
+                .into_iter()
+                .map(|id| Lit::from_index(id, true))
+                .collect(),
+        ),
+        RichDep::And(operands) => Formula::And(operands.iter().map(formula).collect()),
+        RichDep::Or(operands) => Formula::Or(operands.iter().map(formula).collect()),
+        // (A if B else C) is (A and B) or (C and !B) in the reverse dependencies.
+        RichDep::If(x, y, otherwise) if reverse => {
+            let then = Formula::And(vec![formula(x), formula(y)]);
+            match otherwise {
+                Some(z) => Formula::Or(vec![
+                    then,
+                    Formula::And(vec![formula(y).negate(), formula(z)]),
+                ]),
+                None => then,
+            }
+        }
+        // (A unless B else C) is (A and !B) or (C and B) in the reverse dependencies.
+        RichDep::Unless(x, y, otherwise) if reverse => {
+            let then = Formula::And(vec![formula(x), formula(y).negate()]);
+            match otherwise {
+                Some(z) => Formula::Or(vec![then, Formula::And(vec![formula(y), formula(z)])]),
+                None => then,
             }
         }
-        if let Some(conflicts) = repo.get_package_conflicts_by_id(package_id) {
-            for entry in conflicts {
-                if let Some(providers) = repo.get_entry_provider_id(entry) {
-                    for provider_id in providers {
-                        if *provider_id == package_id {
+        // (A if B) is B -> A, and the else branch is !B -> C.
+        RichDep::If(x, y, otherwise) => {
+            let then = Formula::Or(vec![formula(x), formula(y).negate()]);
+            match otherwise {
+                Some(z) => Formula::And(vec![then, Formula::Or(vec![formula(y), formula(z)])]),
+                None => then,
+            }
+        }
+        // (A unless B) is !B -> A, and the else branch is B -> C.
+        RichDep::Unless(x, y, otherwise) => {
+            let then = Formula::Or(vec![formula(x), formula(y)]);
+            match otherwise {
+                Some(z) => Formula::And(vec![
+                    then,
+                    Formula::Or(vec![formula(y).negate(), formula(z)]),
+                ]),
+                None => then,
+            }
+        }
+    }
+}
+
+// The packages in the conditions of the if and unless expressions of the rich dependency.
+fn get_condition_ids(repo: &Repo, dep: &RichDep) -> Vec<IdT> {
+    match dep {
+        RichDep::Simple(_) | RichDep::With(_) | RichDep::Without(..) => Vec::new(),
+        RichDep::And(operands) | RichDep::Or(operands) => operands
+            .iter()
+            .flat_map(|operand| get_condition_ids(repo, operand))
+            .collect(),
+        RichDep::If(x, y, otherwise) | RichDep::Unless(x, y, otherwise) => {
+            let mut ids = get_condition_ids(repo, x);
+            for clause in get_rich_formula(repo, y, false).into_clauses() {
+                ids.extend(clause.iter().map(|lit| lit.index()));
+            }
+            if let Some(z) = otherwise {
+                ids.extend(get_condition_ids(repo, z));
+            }
+            ids
+        }
+    }
+}
+
+// The candidates to satisfy the clause of the rich dependency, the best first. The packages
+// in the conditions come last, e.g. (A unless B) is satisfied by installing A rather than B.
+fn get_rich_candidates(
+    repo: &Repo,
+    options: &SolveOptions,
+    entry: &RpmEntry,
+    clause: &[Lit],
+) -> Vec<IdT> {
+    let mut candidates: Vec<IdT> = clause
+        .iter()
+        .filter(|lit| lit.is_positive())
+        .map(|lit| lit.index())
+        .collect();
+    sort_candidates(repo, options, &mut candidates);
+    if let Ok(dep) = parse_rich_dep(&entry.name) {
+        let conditions = get_condition_ids(repo, &dep);
+        candidates.sort_by_key(|id| conditions.contains(id));
+    }
+    candidates
+}
+
+// The clauses which are all true if the rich dependency is satisfied, or not satisfied if
+// negated. A rich dependency which can not be parsed is never satisfied, like a capability
+// which nothing provides.
+fn get_rich_clauses(repo: &Repo, entry: &RpmEntry, reverse: bool, negate: bool) -> Vec<Vec<Lit>> {
+    match parse_rich_dep(&entry.name) {
+        Ok(dep) if negate => get_rich_formula(repo, &dep, reverse)
+            .negate()
+            .into_clauses(),
+        Ok(dep) => get_rich_formula(repo, &dep, reverse).into_clauses(),
+        Err(_) if negate => Vec::new(),
+        Err(_) => vec![Vec::new()],
+    }
+}
+
+// The rules of the package requiring the rich dependency, or conflicting with it if negated.
+fn get_rich_rules<'a>(
+    repo: &Repo,
+    package_id: IdT,
+    entry: &'a RpmEntry,
+    negate: bool,
+) -> Vec<Rule<'a>> {
+    let mut rules = Vec::new();
+    // The package itself satisfies its requires, but never its conflicts, as the
+    // plain entries do. So its literal is replaced by true or false respectively.
+    let (satisfied, unsatisfied) = (
+        Lit::from_index(package_id, !negate),
+        Lit::from_index(package_id, negate),
+    );
+    // A conflict is a reverse dependency, so (A if B) never requires B to be installed.
+    for mut clause in get_rich_clauses(repo, entry, negate, negate) {
+        if clause.contains(&satisfied) {
+            continue;
+        }
+        clause.retain(|&lit| lit != unsatisfied);
+        clause.push(Lit::from_index(package_id, false));
+        rules.push(Rule {
+            origin: if negate {
+                RuleOrigin::RichConflicts(package_id, entry)
+            } else {
+                RuleOrigin::Requires(package_id, entry)
+            },
+            clause,
+        });
+    }
+    rules
+}
+
+// The packages of the positive literals in the clauses, in order.
+fn get_positive_ids(clauses: &[Vec<Lit>]) -> Vec<IdT> {
+    let mut ids = Vec::new();
+    for lit in clauses.iter().flatten() {
+        if lit.is_positive() && !ids.contains(&lit.index()) {
+            ids.push(lit.index());
+        }
+    }
+    ids
+}
+
+// Two packages with the same name can be installed together only if they are installonly,
+// or they are the same version built for different architectures (multilib).
+fn can_install_both(repo: &Repo, options: &SolveOptions, x: IdT, y: IdT) -> bool {
+    if options.is_installonly(repo, x) && options.is_installonly(repo, y) {
+        return true;
+    }
+    let (x, y) = (
+        repo.get_package_by_id(x).unwrap(),
+        repo.get_package_by_id(y).unwrap(),
+    );
+    x.get_arch() != y.get_arch()
+        && x.get_arch() != "noarch"
+        && y.get_arch() != "noarch"
+        && x.get_epoch() == y.get_epoch()
+        && x.get_ver() == y.get_ver()
+        && x.get_rel() == y.get_rel()
+}
+
+// The packages in the repos which can upgrade the installed package, i.e. those with
+// the same name and arch (or noarch) and a higher EVR.
+fn get_upgrades(repo: &Repo, package_id: IdT) -> Vec<IdT> {
+    let installed = repo.get_package_by_id(package_id).unwrap();
+    repo.get_package_ids_by_name(installed.get_name())
+        .into_iter()
+        .filter(|&id| !repo.is_package_installed(id))
+        .filter(|&id| {
+            let package = repo.get_package_by_id(id).unwrap();
+            (package.get_arch() == installed.get_arch()
+                || package.get_arch() == "noarch"
+                || installed.get_arch() == "noarch")
+                && matches!(
+                    evr_compare(&package.get_evr_entry(), &installed.get_evr_entry()),
+                    Ok(Ordering::Greater)
+                )
+        })
+        .collect()
+}
+
+// The rules derived from the requires, conflicts and obsoletes of a single package.
+fn get_package_rules(repo: &Repo, package_id: IdT) -> Vec<Rule<'_>> {
+    let mut rules = Vec::new();
+    if let Some(requires) = repo.get_package_requires_by_id(package_id) {
+        for entry in requires {
+            if is_rich_dep(&entry.name) {
+                rules.extend(get_rich_rules(repo, package_id, entry, false));
+                continue;
+            }
+            // If nothing provides the entry, or no provider satisfies the version constraint,
+            // the clause only contains the negative literal of the package,
+            // which means it can not be installed.
+            let mut clause: Vec<Lit> = get_providers(repo, entry)
+                .into_iter()
+                .map(|id| Lit::from_index(id, true))
+                .collect();
+            clause.push(Lit::from_index(package_id, false));
+            rules.push(Rule {
+                origin: RuleOrigin::Requires(package_id, entry),
+                clause,
+            });
+        }
+    }
+    if let Some(conflicts) = repo.get_package_conflicts_by_id(package_id) {
+        for entry in conflicts {
+            if is_rich_dep(&entry.name) {
+                rules.extend(get_rich_rules(repo, package_id, entry, true));
+                continue;
+            }
+            for provider_id in get_providers(repo, entry) {
+                if provider_id != package_id {
+                    rules.push(Rule {
+                        origin: RuleOrigin::Conflicts(package_id, entry, provider_id),
+                        clause: vec![
+                            Lit::from_index(provider_id, false),
+                            Lit::from_index(package_id, false),
+                        ],
+                    });
+                }
+            }
+        }
+    }
+    if let Some(obsoletes) = repo.get_package_obsoletes_by_id(package_id) {
+        for entry in obsoletes {
+            for provider_id in get_providers(repo, entry) {
+                if provider_id != package_id {
+                    rules.push(Rule {
+                        origin: RuleOrigin::Obsoletes(package_id, entry, provider_id),
+                        clause: vec![
+                            Lit::from_index(provider_id, false),
+                            Lit::from_index(package_id, false),
+                        ],
+                    });
+                }
+            }
+        }
+    }
+    rules
+}
+
+impl<'a> RuleCache<'a> {
+    pub(crate) fn new(repo: &'a Repo) -> RuleCache<'a> {
+        RuleCache {
+            repo,
+            rules: (0..repo.get_package_count())
+                .map(|_| OnceLock::new())
+                .collect(),
+            supplementers: OnceLock::new(),
+            obsoleters: OnceLock::new(),
+        }
+    }
+
+    // The packages which can replace the installed package by obsoleting it, e.g. when it is
+    // renamed, as opposed to the upgrades with the same name.
+    fn get_obsoleters(&self, package_id: IdT) -> &[IdT] {
+        let obsoleters = self.obsoleters.get_or_init(|| {
+            let repo = self.repo;
+            let mut obsoleters: HashMap<IdT, Vec<IdT>> = HashMap::new();
+            for id in 0..repo.get_package_count() {
+                if repo.is_package_installed(id) {
+                    continue;
+                }
+                for entry in repo.get_package_obsoletes_by_id(id).into_iter().flatten() {
+                    for provider_id in get_providers(repo, entry) {
+                        if !repo.is_package_installed(provider_id) {
                             continue;
                         }
-                        match repo.check_version_constraint(entry, provider_id) {
-                            Ok(true) => formula.add_clause(&[
-                                Lit::from_index(*provider_id, false),
-                                Lit::from_index(package_id, false),
-                            ]),
-                            _ => continue,
+                        let ids = obsoleters.entry(provider_id).or_default();
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
+                    }
+                }
+            }
+            obsoleters
+        });
+        obsoleters.get(&package_id).map_or(&[], Vec::as_slice)
+    }
+
+    fn get_supplementers(&self, package_id: IdT) -> &[IdT] {
+        let supplementers = self.supplementers.get_or_init(|| {
+            let repo = self.repo;
+            let mut supplementers: HashMap<IdT, Vec<IdT>> = HashMap::new();
+            for id in 0..repo.get_package_count() {
+                for entry in repo.get_package_supplements_by_id(id).into_iter().flatten() {
+                    // A rich supplement is checked when any package in it is chosen.
+                    let provider_ids = if is_rich_dep(&entry.name) {
+                        get_positive_ids(&get_rich_clauses(repo, entry, true, false))
+                    } else {
+                        get_providers(repo, entry)
+                    };
+                    for provider_id in provider_ids {
+                        let ids = supplementers.entry(provider_id).or_default();
+                        if !ids.contains(&id) {
+                            ids.push(id);
                         }
                     }
                 }
             }
+            supplementers
+        });
+        supplementers.get(&package_id).map_or(&[], Vec::as_slice)
+    }
+
+    // The packages which may be pulled in by the weak dependencies of the package.
+    fn get_weak_candidates(&self, package_id: IdT) -> Vec<IdT> {
+        let mut candidates: Vec<IdT> = self
+            .repo
+            .get_package_recommends_by_id(package_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|entry| {
+                if is_rich_dep(&entry.name) {
+                    get_positive_ids(&get_rich_clauses(self.repo, entry, false, false))
+                } else {
+                    get_providers(self.repo, entry)
+                }
+            })
+            .collect();
+        candidates.extend_from_slice(self.get_supplementers(package_id));
+        candidates
+    }
+
+    fn get(&self, package_id: IdT) -> &Vec<Rule<'a>> {
+        self.rules[package_id].get_or_init(|| get_package_rules(self.repo, package_id))
+    }
+
+    // Build the rules over the union of the closures of all the jobs,
+    // where each job is given by the ids of its candidate packages.
+    // The ids of the packages in the closures are returned as well.
+    fn get_rules_by_jobs(
+        &self,
+        jobs: &[Vec<IdT>],
+        options: &SolveOptions,
+    ) -> (Vec<Rule<'a>>, Vec<IdT>) {
+        let repo = self.repo;
+        let mut q = VecDeque::new();
+        let mut rules = Vec::new();
+        let mut appeared = HashSet::new();
+        // The installed packages stay installed, unless they are upgraded or obsoleted. So their
+        // dependencies have to be satisfied as well, and they may conflict with the packages
+        // to be installed.
+        for package_id in repo.get_installed_package_ids() {
+            let mut clause = vec![Lit::from_index(package_id, true)];
+            let mut replacements = get_upgrades(repo, package_id);
+            replacements.extend_from_slice(self.get_obsoleters(package_id));
+            for replacement_id in replacements {
+                clause.push(Lit::from_index(replacement_id, true));
+                if appeared.insert(replacement_id) {
+                    q.push_back(replacement_id);
+                }
+            }
+            rules.push(Rule {
+                origin: RuleOrigin::Installed(package_id),
+                clause,
+            });
+            if appeared.insert(package_id) {
+                q.push_back(package_id);
+            }
         }
-        if let Some(obsoletes) = repo.get_package_obsoletes_by_id(package_id) {
-            for entry in obsoletes {
-                if let Some(providers) = repo.get_entry_provider_id(entry) {
-                    for provider_id in providers {
-                        if *provider_id == package_id {
-                            continue;
+        for candidates in jobs {
+            rules.push(Rule {
+                origin: RuleOrigin::Job(candidates.clone()),
+                clause: candidates
+                    .iter()
+                    .map(|&id| Lit::from_index(id, true))
+                    .collect(),
+            });
+            for &package_id in candidates {
+                if appeared.insert(package_id) {
+                    q.push_back(package_id);
+                }
+            }
+        }
+        while let Some(package_id) = q.pop_front() {
+            for rule in self.get(package_id) {
+                // The positive literals of a requires rule are the providers to be visited, and
+                // a rich conflict like (A unless B) is also resolved by installing B.
+                if let RuleOrigin::Requires(..) | RuleOrigin::RichConflicts(..) = rule.origin {
+                    for lit in rule.clause.iter().filter(|lit| lit.is_positive()) {
+                        if appeared.insert(lit.index()) {
+                            q.push_back(lit.index());
                         }
-                        match repo.check_version_constraint(entry, provider_id) {
-                            Ok(true) => formula.add_clause(&[
-                                Lit::from_index(*provider_id, false),
-                                Lit::from_index(package_id, false),
-                            ]),
-                            _ => continue,
+                    }
+                }
+                rules.push(rule.clone());
+            }
+            // The packages pulled in by the weak dependencies are not required, but they
+            // have to be in the formula to be chosen.
+            if options.install_weak_deps {
+                for candidate_id in self.get_weak_candidates(package_id) {
+                    if appeared.insert(candidate_id) {
+                        q.push_back(candidate_id);
+                    }
+                }
+            }
+        }
+        // Forbid installing two different packages with the same name.
+        let mut same_name: HashMap<&String, Vec<IdT>> = HashMap::new();
+        for &package_id in &appeared {
+            let name = repo.get_package_by_id(package_id).unwrap().get_name();
+            same_name.entry(name).or_default().push(package_id);
+        }
+        let mut same_name: Vec<Vec<IdT>> = same_name.into_values().collect();
+        same_name.sort_unstable();
+        for mut ids in same_name {
+            ids.sort_unstable();
+            for (i, &x) in ids.iter().enumerate() {
+                for &y in &ids[i + 1..] {
+                    if !can_install_both(repo, options, x, y) {
+                        rules.push(Rule {
+                            origin: RuleOrigin::SameName(x, y),
+                            clause: vec![Lit::from_index(x, false), Lit::from_index(y, false)],
+                        });
+                    }
+                }
+            }
+        }
+        let mut closure: Vec<IdT> = appeared.into_iter().collect();
+        closure.sort_unstable();
+        (rules, closure)
+    }
+}
+
+impl<'a> RuleSolver<'a> {
+    fn new(repo: &Repo, rules: &[Rule]) -> RuleSolver<'a> {
+        let mut solver = Solver::new();
+        let selector_base = repo.get_package_count();
+        for (index, rule) in rules.iter().enumerate() {
+            let mut clause = rule.clause.clone();
+            clause.push(Lit::from_index(selector_base + index, false));
+            solver.add_clause(&clause);
+        }
+        RuleSolver {
+            solver,
+            selector_base,
+            rule_count: rules.len(),
+        }
+    }
+
+    fn solve(&mut self, enabled: &[usize]) -> Result<bool> {
+        self.solve_with(enabled, &[])
+    }
+
+    // Solve with the enabled rules and some extra assumptions.
+    fn solve_with(&mut self, enabled: &[usize], extra: &[Lit]) -> Result<bool> {
+        let mut assumptions: Vec<Lit> = enabled
+            .iter()
+            .map(|&index| Lit::from_index(self.selector_base + index, true))
+            .collect();
+        assumptions.extend_from_slice(extra);
+        self.solver.assume(&assumptions);
+        Ok(self.solver.solve()?)
+    }
+
+    fn solve_all(&mut self) -> Result<bool> {
+        let enabled: Vec<usize> = (0..self.rule_count).collect();
+        self.solve(&enabled)
+    }
+
+    fn get_model(&self) -> Result<Vec<Lit>> {
+        self.solver
+            .model()
+            .ok_or_else(|| anyhow!("model not found after solving"))
+    }
+
+    fn get_failed_rules(&self) -> Vec<usize> {
+        match self.solver.failed_core() {
+            Some(core) => core
+                .iter()
+                .filter(|lit| lit.index() >= self.selector_base)
+                .map(|lit| lit.index() - self.selector_base)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // The failed core of the solver is not necessarily minimal, so try to drop each rule
+    // of it and keep the rule only if the remaining rules become satisfiable.
+    fn get_minimal_failed_rules(&mut self) -> Result<Vec<usize>> {
+        let mut core = self.get_failed_rules();
+        core.sort_unstable();
+        let mut i = 0;
+        while i < core.len() {
+            let mut candidate = core.clone();
+            candidate.remove(i);
+            if self.solve(&candidate)? {
+                i += 1;
+            } else {
+                let failed: HashSet<usize> = self.get_failed_rules().into_iter().collect();
+                core.retain(|index| failed.contains(index));
+            }
+        }
+        Ok(core)
+    }
+}
+
+// The rule requires an entry which is not provided by any package in the repo.
+fn is_missing_provider(repo: &Repo, rule: &Rule) -> bool {
+    match &rule.origin {
+        // A rich dependency is missing if it can not be satisfied by any packages.
+        RuleOrigin::Requires(_, entry) if is_rich_dep(&entry.name) => rule.clause.len() == 1,
+        RuleOrigin::Requires(_, entry) => repo.get_entry_provider_id(entry).is_none(),
+        _ => false,
+    }
+}
+
+// The packages chosen for the transaction, which are kept as the assumptions of the solver,
+// and a model satisfying them.
+struct Chooser<'s, 'a> {
+    solver: &'s mut RuleSolver<'a>,
+    enabled: &'s [usize],
+    model: Vec<Lit>,
+    assumptions: Vec<Lit>,
+    chosen: HashSet<IdT>,
+}
+
+impl Chooser<'_, '_> {
+    // Choose the first candidate which keeps the formula satisfiable together with the
+    // former choices. The model is updated if the solver is called.
+    fn choose(&mut self, candidates: &[IdT]) -> Result<Option<IdT>> {
+        for &package_id in candidates {
+            self.assumptions.push(Lit::from_index(package_id, true));
+            if is_true(&self.model, package_id) {
+                self.chosen.insert(package_id);
+                return Ok(Some(package_id));
+            }
+            if self.solver.solve_with(self.enabled, &self.assumptions)? {
+                self.model = self.solver.get_model()?;
+                self.chosen.insert(package_id);
+                return Ok(Some(package_id));
+            }
+            self.assumptions.pop();
+        }
+        Ok(None)
+    }
+
+    fn is_chosen(&self, package_id: IdT) -> bool {
+        self.chosen.contains(&package_id)
+    }
+
+    // The clause is satisfied if the chosen packages are installed and no others.
+    fn is_satisfied(&self, clause: &[Lit]) -> bool {
+        clause
+            .iter()
+            .any(|lit| self.is_chosen(lit.index()) == lit.is_positive())
+    }
+}
+
+// Find the minimal number of packages to be installed, by solving with a decreasing upper bound
+// on the number of true package literals. The returned literal enforces the minimal bound.
+fn get_minimal_bound(
+    solver: &mut RuleSolver,
+    enabled: &[usize],
+    closure: &[IdT],
+) -> Result<Option<Lit>> {
+    let model = solver.get_model()?;
+    let mut count = closure.iter().filter(|&&id| is_true(&model, id)).count();
+    let inputs: Vec<Lit> = closure
+        .iter()
+        .map(|&id| Lit::from_index(id, true))
+        .collect();
+    let outputs = build_totalizer(&mut solver.solver, &inputs, count + 1);
+    let mut bound = outputs.get(count).map(|&lit| !lit);
+    while count > 0 {
+        if !solver.solve_with(enabled, &[!outputs[count - 1]])? {
+            break;
+        }
+        let model = solver.get_model()?;
+        count = closure.iter().filter(|&&id| is_true(&model, id)).count();
+        bound = Some(!outputs[count]);
+    }
+    Ok(bound)
+}
+
+// The rich dependency the rule is built from, if any.
+fn get_rich_entry<'a>(rule: &Rule<'a>) -> Option<&'a RpmEntry> {
+    match rule.origin {
+        RuleOrigin::Requires(_, entry) | RuleOrigin::RichConflicts(_, entry)
+            if is_rich_dep(&entry.name) =>
+        {
+            Some(entry)
+        }
+        _ => None,
+    }
+}
+
+// Choose the packages for the rules of the rich dependencies of the package, which are not
+// satisfied by the chosen packages yet. A rule may become unsatisfied after it is checked,
+// e.g. when the condition B of (A if B) is chosen later, so it has to be checked again.
+fn choose_rich_providers<'a>(
+    cache: &RuleCache<'a>,
+    options: &SolveOptions,
+    chooser: &mut Chooser,
+    package_id: IdT,
+) -> Result<Vec<(IdT, &'a RpmEntry)>> {
+    let mut chosen = Vec::new();
+    for rule in cache.get(package_id) {
+        let entry = match get_rich_entry(rule) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if chooser.is_satisfied(&rule.clause) {
+            continue;
+        }
+        let candidates = get_rich_candidates(cache.repo, options, entry, &rule.clause);
+        if let Some(provider_id) = chooser.choose(&candidates)? {
+            chosen.push((provider_id, entry));
+        }
+    }
+    Ok(chosen)
+}
+
+// Whether a supplement of the package is satisfied by the chosen packages.
+fn is_supplementing(repo: &Repo, chooser: &Chooser, package_id: IdT) -> bool {
+    repo.get_package_supplements_by_id(package_id)
+        .into_iter()
+        .flatten()
+        .any(|entry| {
+            if is_rich_dep(&entry.name) {
+                get_rich_clauses(repo, entry, true, false)
+                    .iter()
+                    .all(|clause| chooser.is_satisfied(clause))
+            } else {
+                get_providers(repo, entry)
+                    .iter()
+                    .any(|&id| chooser.is_chosen(id))
+            }
+        })
+}
+
+// Walk the requires from the jobs, and for every job or requirement which is not satisfied by
+// the chosen packages yet, choose the best candidate according to the policy. So the transaction
+// is deterministic, and every package in it is pulled in by exactly one reason.
+// The weak dependencies are only chosen after all the requires, so they never take the place
+// of a better provider of a requirement.
+fn get_best_transaction(
+    cache: &RuleCache,
+    options: &SolveOptions,
+    solver: &mut RuleSolver,
+    enabled: &[usize],
+    assumptions: Vec<Lit>,
+    jobs: &[Vec<IdT>],
+) -> Result<Transaction> {
+    if !solver.solve_with(enabled, &assumptions)? {
+        return Err(anyhow!("the assumptions are not satisfiable"));
+    }
+    let repo = cache.repo;
+    let mut chooser = Chooser {
+        model: solver.get_model()?,
+        solver,
+        enabled,
+        assumptions,
+        chosen: HashSet::new(),
+    };
+    // The packages whose requires are to be walked, and why they are in the transaction. The
+    // installed packages which are kept have no reason, as they are not in the transaction,
+    // but their requires may still need new providers, e.g. when another package is upgraded.
+    let mut q = VecDeque::new();
+    // Keep the installed packages if possible, otherwise choose the best upgrade of them,
+    // or the best package obsoleting them if they can not be upgraded.
+    for package_id in repo.get_installed_package_ids() {
+        if chooser.choose(&[package_id])?.is_some() {
+            q.push_back((package_id, None));
+            continue;
+        }
+        let mut candidates = get_upgrades(repo, package_id);
+        sort_candidates(repo, options, &mut candidates);
+        if let Some(upgrade_id) = chooser.choose(&candidates)? {
+            q.push_back((upgrade_id, Some(Reason::Upgrade(package_id))));
+            continue;
+        }
+        let mut candidates = cache.get_obsoleters(package_id).to_vec();
+        sort_candidates(repo, options, &mut candidates);
+        if let Some(obsoleter_id) = chooser.choose(&candidates)? {
+            q.push_back((obsoleter_id, Some(Reason::Obsoletes(package_id))));
+        }
+    }
+    for candidates in jobs {
+        if candidates.iter().any(|&id| chooser.is_chosen(id)) {
+            continue;
+        }
+        let mut candidates = candidates.clone();
+        sort_candidates(repo, options, &mut candidates);
+        if let Some(package_id) = chooser.choose(&candidates)? {
+            if !repo.is_package_installed(package_id) {
+                q.push_back((package_id, Some(Reason::Job)));
+            }
+        }
+    }
+    let mut transaction = Transaction::default();
+    // The packages in the transaction with rich dependencies, which are checked again
+    // after walking the requires.
+    let mut rich_ids = Vec::new();
+    // The number of the packages in the transaction whose weak dependencies are handled.
+    let mut weak_handled = 0;
+    loop {
+        while let Some((package_id, reason)) = q.pop_front() {
+            if let Some(requires) = repo.get_package_requires_by_id(package_id) {
+                for entry in requires.iter().filter(|entry| !is_rich_dep(&entry.name)) {
+                    let mut candidates = get_providers(repo, entry);
+                    if candidates.iter().any(|&id| chooser.is_chosen(id)) {
+                        continue;
+                    }
+                    sort_candidates(repo, options, &mut candidates);
+                    if let Some(provider_id) = chooser.choose(&candidates)? {
+                        if !repo.is_package_installed(provider_id) {
+                            q.push_back((
+                                provider_id,
+                                Some(Reason::Requires(package_id, entry.clone())),
+                            ));
                         }
                     }
                 }
             }
+            if cache
+                .get(package_id)
+                .iter()
+                .any(|rule| get_rich_entry(rule).is_some())
+            {
+                rich_ids.push(package_id);
+                for (provider_id, entry) in
+                    choose_rich_providers(cache, options, &mut chooser, package_id)?
+                {
+                    if !repo.is_package_installed(provider_id) {
+                        q.push_back((
+                            provider_id,
+                            Some(Reason::Requires(package_id, entry.clone())),
+                        ));
+                    }
+                }
+            }
+            if let Some(reason) = reason {
+                transaction.items.push(TransactionItem {
+                    package_id,
+                    nevra: repo.get_package_by_id(package_id).unwrap().get_nevra(),
+                    repo_id: repo
+                        .get_package_repo_id(package_id)
+                        .unwrap_or_default()
+                        .to_string(),
+                    reason,
+                });
+            }
+        }
+        for &package_id in &rich_ids {
+            for (provider_id, entry) in
+                choose_rich_providers(cache, options, &mut chooser, package_id)?
+            {
+                if !repo.is_package_installed(provider_id) {
+                    q.push_back((
+                        provider_id,
+                        Some(Reason::Requires(package_id, entry.clone())),
+                    ));
+                }
+            }
+        }
+        if !q.is_empty() {
+            continue;
+        }
+        if !options.install_weak_deps || weak_handled == transaction.items.len() {
+            break;
+        }
+        // The weak dependencies of the next package are only chosen if the formula stays
+        // satisfiable, and the requires of the chosen ones are walked before the next package.
+        let package_id = transaction.items[weak_handled].package_id;
+        weak_handled += 1;
+        for entry in repo
+            .get_package_recommends_by_id(package_id)
+            .into_iter()
+            .flatten()
+        {
+            if is_rich_dep(&entry.name) {
+                for clause in get_rich_clauses(repo, entry, false, false) {
+                    if chooser.is_satisfied(&clause) {
+                        continue;
+                    }
+                    let candidates = get_rich_candidates(repo, options, entry, &clause);
+                    if let Some(provider_id) = chooser.choose(&candidates)? {
+                        q.push_back((
+                            provider_id,
+                            Some(Reason::Recommends(package_id, entry.clone())),
+                        ));
+                    }
+                }
+                continue;
+            }
+            let mut candidates = get_providers(repo, entry);
+            if candidates.iter().any(|&id| chooser.is_chosen(id)) {
+                continue;
+            }
+            sort_candidates(repo, options, &mut candidates);
+            if let Some(provider_id) = chooser.choose(&candidates)? {
+                q.push_back((
+                    provider_id,
+                    Some(Reason::Recommends(package_id, entry.clone())),
+                ));
+            }
         }
+        let mut supplementers = cache.get_supplementers(package_id).to_vec();
+        sort_candidates(repo, options, &mut supplementers);
+        for supplementer_id in supplementers {
+            if chooser.is_chosen(supplementer_id)
+                || !is_supplementing(repo, &chooser, supplementer_id)
+            {
+                continue;
+            }
+            if chooser.choose(&[supplementer_id])?.is_some() {
+                q.push_back((supplementer_id, Some(Reason::Supplements(package_id))));
+            }
+        }
+    }
+    Ok(transaction)
+}
+
+fn get_problem(repo: &Repo, rule: &Rule) -> Option<Problem> {
+    let nevra = |id: IdT| repo.get_package_by_id(id).unwrap().get_nevra();
+    match &rule.origin {
+        RuleOrigin::Job(_) => None,
+        RuleOrigin::Requires(package_id, entry) => {
+            if rule.clause.len() == 1 {
+                Some(Problem::NothingProvides {
+                    package: nevra(*package_id),
+                    entry: entry.to_string(),
+                })
+            } else {
+                Some(Problem::RequiresUninstallable {
+                    package: nevra(*package_id),
+                    entry: entry.to_string(),
+                })
+            }
+        }
+        RuleOrigin::Conflicts(package_id, entry, provider_id) => Some(Problem::Conflicts {
+            package: nevra(*package_id),
+            entry: entry.to_string(),
+            conflicting: nevra(*provider_id),
+        }),
+        RuleOrigin::RichConflicts(package_id, entry) => Some(Problem::Conflicts {
+            package: nevra(*package_id),
+            entry: entry.to_string(),
+            conflicting: rule
+                .clause
+                .iter()
+                .filter(|lit| !lit.is_positive() && lit.index() != *package_id)
+                .map(|lit| nevra(lit.index()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }),
+        RuleOrigin::Obsoletes(package_id, entry, provider_id) => Some(Problem::Obsoletes {
+            package: nevra(*package_id),
+            entry: entry.to_string(),
+            obsoleted: nevra(*provider_id),
+        }),
+        RuleOrigin::SameName(x, y) => Some(Problem::SameName {
+            package: nevra(*x),
+            other: nevra(*y),
+        }),
+        RuleOrigin::Installed(package_id) => Some(Problem::Installed {
+            package: nevra(*package_id),
+        }),
     }
-    Ok(formula)
 }
 
-pub fn check_package_satisfiability_in_repo(repo: &Repo, package_name: &String) -> Result<ReturnValue> {
-    if let Some(package_id) = repo.get_package_id_by_name(&package_name) {
-        if let Ok(formula) = get_formula_by_package_id(repo, package_id) {
-            let mut solver = Solver::new();
-            solver.add_formula(&formula);
-            solver.assume(&[Lit::from_index(package_id, true)]);
-            match solver.solve() {
-                Ok(true) => Ok(ReturnValue::Satisfied),
-                _ => Ok(ReturnValue::Unsatisfied),
+// Explain why the rules are unsatisfiable, the solver must have failed with all the rules enabled.
+fn get_unsatisfied_result(
+    repo: &Repo,
+    solver: &mut RuleSolver,
+    rules: &[Rule],
+) -> Result<SolveResult> {
+    // Collect every missing provider taking part in the failure: disable the
+    // missing providers found in the core, and solve again until the failure
+    // is caused by something else or the formula becomes satisfiable.
+    let mut core = solver.get_minimal_failed_rules()?;
+    let mut enabled: Vec<usize> = (0..rules.len()).collect();
+    let mut missing = Vec::new();
+    loop {
+        let found: Vec<usize> = core
+            .iter()
+            .copied()
+            .filter(|&index| is_missing_provider(repo, &rules[index]))
+            .collect();
+        if found.is_empty() {
+            break;
+        }
+        enabled.retain(|index| !found.contains(index));
+        missing.extend(found);
+        if solver.solve(&enabled)? {
+            missing.sort_unstable();
+            return Ok(SolveResult::MissingProviders(
+                missing
+                    .into_iter()
+                    .filter_map(|index| get_problem(repo, &rules[index]))
+                    .collect(),
+            ));
+        }
+        core = solver.get_minimal_failed_rules()?;
+    }
+    // The remaining core fails without any missing provider, so it is reported
+    // together with the missing providers found before it.
+    missing.sort_unstable();
+    let mut problems: Vec<Problem> = missing
+        .into_iter()
+        .chain(core)
+        .filter_map(|index| get_problem(repo, &rules[index]))
+        .collect();
+    // Show the root causes before the requires chains leading to them.
+    problems.sort_by_key(|problem| matches!(problem, Problem::RequiresUninstallable { .. }));
+    Ok(SolveResult::Unsatisfied(problems))
+}
+
+// Solve all the jobs together and return the packages to be installed,
+// or the problems which make the jobs unsatisfiable.
+pub fn solve_jobs_in_repo(
+    repo: &Repo,
+    jobs: &[Job],
+    options: &SolveOptions,
+) -> Result<SolveResult> {
+    let mut candidates = Vec::new();
+    for job in jobs {
+        match job {
+            Job::Install(package_name) => {
+                let ids = repo.get_package_ids_by_name(package_name);
+                if ids.is_empty() {
+                    return Ok(SolveResult::PackageNotFound(package_name.clone()));
+                }
+                candidates.push(ids);
+            }
+            Job::InstallPackage(package_id) => {
+                if repo.get_package_by_id(*package_id).is_none() {
+                    return Ok(SolveResult::PackageNotFound(package_id.to_string()));
+                }
+                candidates.push(vec![*package_id]);
             }
-        } else {
-            Ok(ReturnValue::VersionConflict)
         }
+    }
+    let cache = RuleCache::new(repo);
+    let (rules, closure) = cache.get_rules_by_jobs(&candidates, options);
+    repo.check_file_providers()?;
+    let mut solver = RuleSolver::new(repo, &rules);
+    if solver.solve_all()? {
+        let enabled: Vec<usize> = (0..rules.len()).collect();
+        let mut assumptions = Vec::new();
+        if options.minimize {
+            if let Some(bound) = get_minimal_bound(&mut solver, &enabled, &closure)? {
+                assumptions.push(bound);
+            }
+        }
+        Ok(SolveResult::Satisfied(get_best_transaction(
+            &cache,
+            options,
+            &mut solver,
+            &enabled,
+            assumptions,
+            &candidates,
+        )?))
     } else {
-        println!(
-            "Error: the package {} is not found in the repository!",
-            package_name
-        );
-        Ok(ReturnValue::PackageNotFound)
+        get_unsatisfied_result(repo, &mut solver, &rules)
+    }
+}
+
+// Check whether the package can be installed, and return the problems if it can not.
+// The rules shared with the other packages are taken from the cache.
+pub(crate) fn check_package_in_repo(
+    cache: &RuleCache,
+    options: &SolveOptions,
+    package_id: IdT,
+) -> Result<Option<Vec<Problem>>> {
+    let repo = cache.repo;
+    // The weak dependencies never make a package uninstallable, so they are left out.
+    let options = SolveOptions {
+        install_weak_deps: false,
+        ..options.clone()
+    };
+    let (rules, _) = cache.get_rules_by_jobs(&[vec![package_id]], &options);
+    repo.check_file_providers()?;
+    let mut solver = RuleSolver::new(repo, &rules);
+    if solver.solve_all()? {
+        return Ok(None);
+    }
+    match get_unsatisfied_result(repo, &mut solver, &rules)? {
+        SolveResult::Unsatisfied(problems) | SolveResult::MissingProviders(problems) => {
+            Ok(Some(problems))
+        }
+        _ => Err(anyhow!("unexpected result of unsatisfiable rules")),
+    }
+}
+
+// Solve the dependencies of the package and return the packages to be installed,
+// or the problems which make the package uninstallable.
+pub fn get_transaction_in_repo(repo: &Repo, package_name: &str) -> Result<SolveResult> {
+    solve_jobs_in_repo(
+        repo,
+        &[Job::Install(package_name.to_string())],
+        &SolveOptions::default(),
+    )
+}
+
+pub fn check_package_satisfiability_in_repo(
+    repo: &Repo,
+    package_name: &String,
+) -> Result<ReturnValue> {
+    match get_transaction_in_repo(repo, package_name)? {
+        SolveResult::Satisfied(_) => Ok(ReturnValue::Satisfied),
+        SolveResult::Unsatisfied(problems) => {
+            if problems
+                .iter()
+                .any(|problem| matches!(problem, Problem::NothingProvides { .. }))
+            {
+                Ok(ReturnValue::VersionConflict)
+            } else {
+                Ok(ReturnValue::Unsatisfied)
+            }
+        }
+        SolveResult::MissingProviders(_) => Ok(ReturnValue::Unsatisfied),
+        SolveResult::PackageNotFound(_) => {
+            println!(
+                "Error: the package {} is not found in the repository!",
+                package_name
+            );
+            Ok(ReturnValue::PackageNotFound)
+        }
     }
 }
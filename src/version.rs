@@ -1,6 +1,6 @@
 use crate::repo::RpmEntry;
 use anyhow::Result;
-use std::cmp::min;
+use std::cmp::Ordering;
 
 pub enum Flag {
     LE,
@@ -10,139 +10,108 @@ pub enum Flag {
     GE,
 }
 
-fn split_string_to_alpha_and_numeric_sections(str: &String) -> Result<Vec<String>> {
-    let mut tmp = String::new();
-    // flag: 0 - empty
-    //       1 - alpha
-    //       2 - numeric
-    let mut flag = 0;
-    let mut result: Vec<String> = Vec::new();
-    for ch in str.chars() {
-        if ch.is_alphabetic() {
-            match flag {
-                1 => tmp = tmp + &ch.to_string(),
-                2 => {
-                    result.push(tmp.clone());
-                    tmp.clear();
-                    flag = 1;
-                    tmp = tmp + &ch.to_string()
-                }
-                _ => {
-                    flag = 1;
-                    tmp = tmp + &ch.to_string()
-                }
+// Compare two versions or releases the same way as rpmvercmp of rpm. The strings are split
+// into runs of ASCII letters and digits, everything else separates them. Numeric runs are
+// newer than alphabetic ones and compared by their digits without the leading zeros, so they
+// never overflow. A tilde sorts before anything, even the end of the string, e.g. 1.0~rc1 is
+// older than 1.0. A caret sorts after the end of the string but before anything else,
+// e.g. 1.0^git1 is newer than 1.0 but older than 1.0.1.
+pub fn rpmvercmp(x: &str, y: &str) -> Ordering {
+    if x == y {
+        return Ordering::Equal;
+    }
+    let (x, y) = (x.as_bytes(), y.as_bytes());
+    let is_separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+    let (mut i, mut j) = (0, 0);
+    while i < x.len() || j < y.len() {
+        while x.get(i).is_some_and(is_separator) {
+            i += 1;
+        }
+        while y.get(j).is_some_and(is_separator) {
+            j += 1;
+        }
+        let (a, b) = (x.get(i), y.get(j));
+        if a == Some(&b'~') || b == Some(&b'~') {
+            if a != Some(&b'~') {
+                return Ordering::Greater;
             }
-        } else if ch.is_numeric() {
-            match flag {
-                1 => {
-                    result.push(tmp.clone());
-                    tmp.clear();
-                    flag = 2;
-                    tmp = tmp + &ch.to_string()
-                }
-                2 => tmp = tmp + &ch.to_string(),
-                _ => {
-                    flag = 2;
-                    tmp = tmp + &ch.to_string()
-                }
+            if b != Some(&b'~') {
+                return Ordering::Less;
             }
-        } else if flag > 0 {
-            result.push(tmp.clone());
-            tmp.clear();
-            flag = 0;
+            i += 1;
+            j += 1;
+            continue;
         }
-    }
-    if flag > 0 {
-        result.push(tmp.clone());
-    }
-    Ok(result)
-}
-
-fn is_alphabetic_string(str: &String) -> Result<bool> {
-    for ch in str.chars() {
-        if !ch.is_alphabetic() {
-            return Ok(false);
+        if a == Some(&b'^') || b == Some(&b'^') {
+            if a.is_none() {
+                return Ordering::Less;
+            }
+            if b.is_none() {
+                return Ordering::Greater;
+            }
+            if a != Some(&b'^') {
+                return Ordering::Greater;
+            }
+            if b != Some(&b'^') {
+                return Ordering::Less;
+            }
+            i += 1;
+            j += 1;
+            continue;
         }
-    }
-    Ok(true)
-}
-
-fn is_equal_section(x: &String, y: &String) -> Result<bool> {
-    match (is_alphabetic_string(&x)?, is_alphabetic_string(&y)?) {
-        (true, true) => Ok(x == y),
-        (false, false) => match (x.parse::<u32>(), y.parse::<u32>()) {
-            (Ok(x_num), Ok(y_num)) => Ok(x_num == y_num),
-            (_, _) => Ok(false),
-        },
-        (_, _) => Ok(false),
-    }
-}
-
-fn section_compare(x: &String, y: &String, op: Flag) -> Result<bool> {
-    match (is_alphabetic_string(&x)?, is_alphabetic_string(&y)?) {
-        // Both of the sections are alphabetic.
-        // Compare like strcmp function.
-        (true, true) => match op {
-            Flag::LT => Ok(x.cmp(&y).is_lt()),
-            Flag::LE => Ok(x.cmp(&y).is_le()),
-            Flag::EQ => Ok(x.cmp(&y).is_eq()),
-            Flag::GE => Ok(x.cmp(&y).is_ge()),
-            Flag::GT => Ok(x.cmp(&y).is_gt()),
-        },
-        // If one of the sections is a number, while the other is alphabetic,
-        // the numeric elements is considered newer.
-        // (alphabetic, numeric)
-        (true, false) => match op {
-            Flag::LT | Flag::LE => Ok(true),
-            _ => Ok(false),
-        },
-        // (numeric, alphabetic)
-        (false, true) => match op {
-            Flag::GE | Flag::GT => Ok(true),
-            _ => Ok(false),
-        },
-        // Both of the sections are numbers.
-        (false, false) => match (x.parse::<i32>(), y.parse::<i32>()) {
-            (Ok(x_num), Ok(y_num)) => match op {
-                Flag::LT => Ok(x_num < y_num),
-                Flag::LE => Ok(x_num <= y_num),
-                Flag::EQ => Ok(x_num == y_num),
-                Flag::GE => Ok(x_num >= y_num),
-                Flag::GT => Ok(x_num > y_num),
-            },
-            (_, _) => Ok(true),
-        },
-    }
-}
-
-fn label_compare(x: &String, y: &String, op: Flag) -> Result<bool> {
-    let x_vec = split_string_to_alpha_and_numeric_sections(x)?;
-    let y_vec = split_string_to_alpha_and_numeric_sections(y)?;
-    let limit = min(x_vec.len(), y_vec.len());
-    for i in 0..limit {
-        if is_equal_section(&x_vec[i], &y_vec[i])? {
-            if i == limit - 1 {
-                match op {
-                    Flag::LT | Flag::GT => {
-                        if x_vec.len() == y_vec.len() {
-                            return Ok(false);
-                        }
-                    }
-                    _ => continue,
-                }
+        // Either of the strings ends.
+        let a = match (a, b) {
+            (Some(a), Some(_)) => a,
+            _ => break,
+        };
+        // The run of x decides whether both runs are numeric or alphabetic.
+        let is_numeric = a.is_ascii_digit();
+        let is_run = |c: &u8| {
+            if is_numeric {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
             }
-        } else {
-            return section_compare(&x_vec[i], &y_vec[i], op);
+        };
+        let x_end = i + x[i..].iter().take_while(|c| is_run(c)).count();
+        let y_end = j + y[j..].iter().take_while(|c| is_run(c)).count();
+        let (mut x_run, mut y_run) = (&x[i..x_end], &y[j..y_end]);
+        (i, j) = (x_end, y_end);
+        // The runs are of different types, numeric runs are newer than alphabetic ones.
+        if y_run.is_empty() {
+            return if is_numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
         }
-    }
-    if x_vec.len() != y_vec.len() {
-        match op {
-            Flag::LT | Flag::LE => return Ok(x_vec.len() < y_vec.len()),
-            Flag::EQ => return Ok(false),
-            Flag::GE | Flag::GT => return Ok(x_vec.len() > y_vec.len()),
+        if is_numeric {
+            let trim = |run: &[u8]| -> usize { run.iter().take_while(|&&c| c == b'0').count() };
+            x_run = &x_run[trim(x_run)..];
+            y_run = &y_run[trim(y_run)..];
+            match x_run.len().cmp(&y_run.len()) {
+                Ordering::Equal => (),
+                ordering => return ordering,
+            }
+        }
+        match x_run.cmp(y_run) {
+            Ordering::Equal => (),
+            ordering => return ordering,
         }
     }
-    Ok(true)
+    // The string with anything left is newer.
+    (i < x.len()).cmp(&(j < y.len()))
+}
+
+fn label_compare(x: &String, y: &String, op: Flag) -> Result<bool> {
+    let ordering = rpmvercmp(x, y);
+    Ok(match op {
+        Flag::LT => ordering.is_lt(),
+        Flag::LE => ordering.is_le(),
+        Flag::EQ => ordering.is_eq(),
+        Flag::GE => ordering.is_ge(),
+        Flag::GT => ordering.is_gt(),
+    })
 }
 
 pub fn version_compare(x: &RpmEntry, y: &RpmEntry, op: Flag) -> Result<bool> {
@@ -222,10 +191,133 @@ pub fn version_compare(x: &RpmEntry, y: &RpmEntry, op: Flag) -> Result<bool> {
     }
 }
 
+pub fn evr_compare(x: &RpmEntry, y: &RpmEntry) -> Result<Ordering> {
+    if version_compare(x, y, Flag::LT)? {
+        Ok(Ordering::Less)
+    } else if version_compare(x, y, Flag::GT)? {
+        Ok(Ordering::Greater)
+    } else {
+        Ok(Ordering::Equal)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // The test cases of rpmvercmp in tests/rpmvercmp.at of rpm.
+    const RPMVERCMP_CASES: &[(&str, &str, i32)] = &[
+        ("1.0", "1.0", 0),
+        ("1.0", "2.0", -1),
+        ("2.0", "1.0", 1),
+        ("2.0.1", "2.0.1", 0),
+        ("2.0", "2.0.1", -1),
+        ("2.0.1", "2.0", 1),
+        ("2.0.1a", "2.0.1a", 0),
+        ("2.0.1a", "2.0.1", 1),
+        ("2.0.1", "2.0.1a", -1),
+        ("5.5p1", "5.5p1", 0),
+        ("5.5p1", "5.5p2", -1),
+        ("5.5p2", "5.5p1", 1),
+        ("5.5p10", "5.5p10", 0),
+        ("5.5p1", "5.5p10", -1),
+        ("5.5p10", "5.5p1", 1),
+        ("10xyz", "10.1xyz", -1),
+        ("10.1xyz", "10xyz", 1),
+        ("xyz10", "xyz10", 0),
+        ("xyz10", "xyz10.1", -1),
+        ("xyz10.1", "xyz10", 1),
+        ("xyz.4", "xyz.4", 0),
+        ("xyz.4", "8", -1),
+        ("8", "xyz.4", 1),
+        ("xyz.4", "2", -1),
+        ("2", "xyz.4", 1),
+        ("5.5p2", "5.6p1", -1),
+        ("5.6p1", "5.5p2", 1),
+        ("5.6p1", "6.5p1", -1),
+        ("6.5p1", "5.6p1", 1),
+        ("6.0.rc1", "6.0", 1),
+        ("6.0", "6.0.rc1", -1),
+        ("10b2", "10a1", 1),
+        ("10a2", "10b2", -1),
+        ("1.0aa", "1.0aa", 0),
+        ("1.0a", "1.0aa", -1),
+        ("1.0aa", "1.0a", 1),
+        ("10.0001", "10.0001", 0),
+        ("10.0001", "10.1", 0),
+        ("10.1", "10.0001", 0),
+        ("10.0001", "10.0039", -1),
+        ("10.0039", "10.0001", 1),
+        ("4.999.9", "5.0", -1),
+        ("5.0", "4.999.9", 1),
+        ("20101121", "20101121", 0),
+        ("20101121", "20101122", -1),
+        ("20101122", "20101121", 1),
+        ("2_0", "2_0", 0),
+        ("2.0", "2_0", 0),
+        ("2_0", "2.0", 0),
+        // RhBug:178798
+        ("a", "a", 0),
+        ("a+", "a+", 0),
+        ("a+", "a_", 0),
+        ("a_", "a+", 0),
+        ("+a", "+a", 0),
+        ("+a", "_a", 0),
+        ("_a", "+a", 0),
+        ("+_", "+_", 0),
+        ("_+", "+_", 0),
+        ("_+", "_", 0),
+        ("+", "_", 0),
+        ("_", "+", 0),
+        // Tilde sorting.
+        ("1.0~rc1", "1.0~rc1", 0),
+        ("1.0~rc1", "1.0", -1),
+        ("1.0", "1.0~rc1", 1),
+        ("1.0~rc1", "1.0~rc2", -1),
+        ("1.0~rc2", "1.0~rc1", 1),
+        ("1.0~rc1~git123", "1.0~rc1~git123", 0),
+        ("1.0~rc1~git123", "1.0~rc1", -1),
+        ("1.0~rc1", "1.0~rc1~git123", 1),
+        // Caret sorting.
+        ("1.0^", "1.0^", 0),
+        ("1.0^", "1.0", 1),
+        ("1.0", "1.0^", -1),
+        ("1.0^git1", "1.0^git1", 0),
+        ("1.0^git1", "1.0", 1),
+        ("1.0", "1.0^git1", -1),
+        ("1.0^git1", "1.0^git2", -1),
+        ("1.0^git2", "1.0^git1", 1),
+        ("1.0^git1", "1.01", -1),
+        ("1.01", "1.0^git1", 1),
+        ("1.0^20160101", "1.0^20160101", 0),
+        ("1.0^20160101", "1.0.1", -1),
+        ("1.0.1", "1.0^20160101", 1),
+        ("1.0^20160101^git1", "1.0^20160101^git1", 0),
+        ("1.0^20160102", "1.0^20160101^git1", 1),
+        ("1.0^20160101^git1", "1.0^20160102", -1),
+        // Tilde and caret sorting.
+        ("1.0~rc1^git1", "1.0~rc1^git1", 0),
+        ("1.0~rc1^git1", "1.0~rc1", 1),
+        ("1.0~rc1", "1.0~rc1^git1", -1),
+        ("1.0^git1~pre", "1.0^git1~pre", 0),
+        ("1.0^git1", "1.0^git1~pre", 1),
+        ("1.0^git1~pre", "1.0^git1", -1),
+        // The oddities documented by rpm, RhBug:811992.
+        ("1b.fc17", "1b.fc17", 0),
+        ("1b.fc17", "1.fc17", -1),
+        ("1.fc17", "1b.fc17", 1),
+        ("1g.fc17", "1g.fc17", 0),
+        ("1g.fc17", "1.fc17", 1),
+        ("1.fc17", "1g.fc17", -1),
+        // Non-ASCII characters are separators, so these are all the same.
+        ("1.1.\u{3b1}", "1.1.\u{3b1}", 0),
+        ("1.1.\u{3b1}", "1.1.\u{3b2}", 0),
+        ("1.1.\u{3b2}", "1.1.\u{3b1}", 0),
+        ("1.1.\u{3b1}\u{3b1}", "1.1.\u{3b1}", 0),
+        ("1.1.\u{3b1}", "1.1.\u{3b2}\u{3b2}", 0),
+        ("1.1.\u{3b2}\u{3b2}", "1.1.\u{3b1}\u{3b1}", 0),
+    ];
+
     #[test]
     fn test_version_compare() -> Result<()> {
         let e1 = RpmEntry {
@@ -298,4 +390,17 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_rpmvercmp() {
+        for &(x, y, expected) in RPMVERCMP_CASES {
+            assert_eq!(rpmvercmp(x, y), expected.cmp(&0), "{} vs {}", x, y);
+        }
+        // Numeric runs longer than any integer type are compared as well.
+        assert_eq!(
+            rpmvercmp("1.99999999999999999999999", "1.100000000000000000000000"),
+            Ordering::Less
+        );
+        assert_eq!(rpmvercmp("2147483648", "2147483647"), Ordering::Greater);
+    }
 }
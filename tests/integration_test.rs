@@ -1,6 +1,15 @@
 use anyhow::Result;
-use rust_solv::{repo, solve};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rust_solv::{cache, mirror, repo, repoclosure, solve};
+use sha2::{Digest, Sha256};
 use std::fs;
+use std::io::{BufReader, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 #[test]
 fn test_dependency_unsatisfied() -> Result<()> {
@@ -76,3 +85,1091 @@ fn test_satisfied() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_transaction() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let nevras: Vec<&str> = transaction
+                .get_items()
+                .iter()
+                .map(|item| item.nevra.as_str())
+                .collect();
+            assert_eq!(
+                nevras,
+                vec![
+                    "A-2.1.3-22.oe2203.x86_64",
+                    "B-2.1.3-22.oe2203.x86_64",
+                    "C-2.1.3-22.oe2203.x86_64",
+                    "D-2.1.3-22.oe2203.x86_64",
+                ]
+            );
+            assert!(matches!(
+                transaction.get_items()[0].reason,
+                solve::Reason::Job
+            ));
+            assert!(matches!(
+                transaction.get_items()[2].reason,
+                solve::Reason::Requires(0, _)
+            ));
+        }
+        _ => panic!("package A should be satisfied"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_problems() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/dependency-unsatisfied.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Unsatisfied(problems) => {
+            let messages: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            assert_eq!(
+                messages[0],
+                "package C-2.1.3-22.oe2203.x86_64 conflicts with D provided by D-2.1.3-22.oe2203.x86_64"
+            );
+        }
+        _ => panic!("package A should be unsatisfied"),
+    }
+
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/version-unsatisfied.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Unsatisfied(problems) => {
+            let messages: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "nothing provides B >= 2.2.1-2333 needed by C-2.1.3-22.oe2203.x86_64",
+                    "package A-2.1.3-22.oe2203.x86_64 requires C, but none of the providers can be installed",
+                ]
+            );
+        }
+        _ => panic!("package A should be unsatisfied"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_missing_providers() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/missing-provider.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::MissingProviders(problems) => {
+            let messages: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "nothing provides libfoo.so.2()(64bit) needed by A-2.1.3-22.oe2203.x86_64",
+                    "nothing provides libbar needed by A-2.1.3-22.oe2203.x86_64",
+                    "nothing provides libbaz >= 1.0 needed by B-2.1.3-22.oe2203.x86_64",
+                ]
+            );
+        }
+        _ => panic!("package A should miss providers"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_jobs() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let jobs = vec![
+        solve::Job::Install("C".to_string()),
+        solve::Job::Install("D".to_string()),
+    ];
+    match solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let nevras: Vec<&str> = transaction
+                .get_items()
+                .iter()
+                .map(|item| item.nevra.as_str())
+                .collect();
+            assert_eq!(
+                nevras,
+                vec![
+                    "C-2.1.3-22.oe2203.x86_64",
+                    "D-2.1.3-22.oe2203.x86_64",
+                    "B-2.1.3-22.oe2203.x86_64",
+                ]
+            );
+        }
+        _ => panic!("packages C and D should be satisfied together"),
+    }
+
+    // C and D can be installed alone, but not together.
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/dependency-unsatisfied.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let jobs = vec![
+        solve::Job::Install("C".to_string()),
+        solve::Job::Install("D".to_string()),
+    ];
+    match solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())? {
+        solve::SolveResult::Unsatisfied(problems) => assert_eq!(
+            problems.iter().map(|p| p.to_string()).collect::<Vec<String>>(),
+            vec!["package C-2.1.3-22.oe2203.x86_64 conflicts with D provided by D-2.1.3-22.oe2203.x86_64"]
+        ),
+        _ => panic!("packages C and D should conflict"),
+    }
+    let jobs = vec![solve::Job::Install("E".to_string())];
+    assert!(matches!(
+        solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())?,
+        solve::SolveResult::PackageNotFound(name) if name == "E"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_same_name() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/same-name.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    // A needs B >= 2.0, while C needs B = 1.0.
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Unsatisfied(problems) => assert_eq!(
+            problems[0].to_string(),
+            "cannot install both B-1.0-1.x86_64 and B-2.0-1.x86_64"
+        ),
+        _ => panic!("package A should be unsatisfied"),
+    }
+    // Two versions of an installonly package can be installed together.
+    let jobs = vec![solve::Job::Install("D".to_string())];
+    assert!(matches!(
+        solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())?,
+        solve::SolveResult::Satisfied(_)
+    ));
+    let options = solve::SolveOptions {
+        installonlypkgs: Vec::new(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        solve::solve_jobs_in_repo(&repo, &jobs, &options)?,
+        solve::SolveResult::Unsatisfied(_)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_prefer_best_candidates() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/prefer.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let options = solve::SolveOptions {
+        arches: vec![
+            "x86_64".to_string(),
+            "noarch".to_string(),
+            "i686".to_string(),
+        ],
+        ..Default::default()
+    };
+    let nevras = |jobs: &[&str]| -> Result<Vec<String>> {
+        let jobs: Vec<solve::Job> = jobs
+            .iter()
+            .map(|name| solve::Job::Install(name.to_string()))
+            .collect();
+        match solve::solve_jobs_in_repo(&repo, &jobs, &options)? {
+            solve::SolveResult::Satisfied(transaction) => Ok(transaction
+                .get_items()
+                .iter()
+                .map(|item| item.nevra.clone())
+                .collect()),
+            _ => panic!("jobs should be satisfied"),
+        }
+    };
+    assert_eq!(
+        nevras(&["A"])?,
+        vec!["A-1.0-1.x86_64", "B-2.0-1.x86_64", "L-1.0-1.x86_64"]
+    );
+    assert_eq!(
+        nevras(&["A", "C"])?,
+        vec![
+            "A-1.0-1.x86_64",
+            "C-1.0-1.x86_64",
+            "B-1.5-1.x86_64",
+            "L-1.0-1.x86_64"
+        ]
+    );
+    assert_eq!(nevras(&["B"])?, vec!["B-2.0-1.x86_64"]);
+    Ok(())
+}
+
+#[test]
+fn test_minimize() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/minimize.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let jobs = vec![solve::Job::Install("A".to_string())];
+    let nevras = |options: &solve::SolveOptions| -> Result<Vec<String>> {
+        match solve::solve_jobs_in_repo(&repo, &jobs, options)? {
+            solve::SolveResult::Satisfied(transaction) => Ok(transaction
+                .get_items()
+                .iter()
+                .map(|item| item.nevra.clone())
+                .collect()),
+            _ => panic!("package A should be satisfied"),
+        }
+    };
+    // Both B and S provide mta, B is preferred by name but pulls in X and Y.
+    assert_eq!(nevras(&solve::SolveOptions::default())?.len(), 4);
+    let options = solve::SolveOptions {
+        minimize: true,
+        ..Default::default()
+    };
+    assert_eq!(nevras(&options)?, vec!["A-1.0-1.x86_64", "S-1.0-1.x86_64"]);
+    Ok(())
+}
+
+#[test]
+fn test_repo_closure() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/missing-provider.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let report = repoclosure::check_repo_closure(&repo, &solve::SolveOptions::default())?;
+    assert_eq!(report.checked, 2);
+    assert_eq!(
+        report.broken,
+        vec!["A-2.1.3-22.oe2203.x86_64", "B-2.1.3-22.oe2203.x86_64"]
+    );
+    assert_eq!(
+        report.reasons["nothing provides libbaz >= 1.0 needed by B-2.1.3-22.oe2203.x86_64"],
+        vec!["A-2.1.3-22.oe2203.x86_64", "B-2.1.3-22.oe2203.x86_64"]
+    );
+    assert_eq!(report.reasons.len(), 3);
+
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let report = repoclosure::check_repo_closure(&repo, &solve::SolveOptions::default())?;
+    assert_eq!(report.checked, 4);
+    assert!(report.broken.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_repo_closure_parallel() -> Result<()> {
+    for fixture in [
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/missing-provider.xml"),
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/dependency-unsatisfied.xml"
+        ),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/same-name.xml"),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"),
+    ] {
+        let xml = fs::read_to_string(fixture)?;
+        let repo = repo::Repo::from_str(&xml)?;
+        let options = solve::SolveOptions::default();
+        assert_eq!(
+            repoclosure::check_repo_closure_parallel(&repo, &options)?,
+            repoclosure::check_repo_closure(&repo, &options)?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_package_record() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/package-record.xml"
+    ))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    let package = repo.get_package_by_id(0).unwrap();
+    assert_eq!(package.get_arch(), "x86_64");
+    let checksum = package.get_checksum().unwrap();
+    assert_eq!(checksum.checksum_type, "sha256");
+    assert_eq!(checksum.pkgid.as_deref(), Some("YES"));
+    assert_eq!(
+        checksum.value,
+        "9d1c9b4a55c4f4a6b11d2e70b1b2f3c1c5a7e3e4d0b8e7f8a1c2d3e4f5a6b7c8"
+    );
+    assert_eq!(
+        package.get_summary(),
+        Some("A library for testing & checking")
+    );
+    assert_eq!(
+        package.get_description(),
+        Some("A is a package\nused by the tests.")
+    );
+    assert_eq!(package.get_url(), Some("https://example.org/a"));
+    let time = package.get_time().unwrap();
+    assert_eq!((time.file, time.build), (1648113394, 1647964829));
+    let size = package.get_size().unwrap();
+    assert_eq!(
+        (size.package, size.installed, size.archive),
+        (112344, 348091, 351032)
+    );
+    assert_eq!(
+        package.get_location_href(),
+        Some("Packages/A-2.1.3-22.oe2203.x86_64.rpm")
+    );
+    assert_eq!(package.get_license(), Some("MIT and BSD"));
+    assert_eq!(package.get_vendor(), Some("openEuler"));
+    assert_eq!(package.get_group(), Some("Unspecified"));
+    assert_eq!(package.get_buildhost(), Some("dc-64g.compass-ci"));
+    assert_eq!(package.get_sourcerpm(), Some("A-2.1.3-22.oe2203.src.rpm"));
+    let header_range = package.get_header_range().unwrap();
+    assert_eq!((header_range.start, header_range.end), (4504, 21793));
+    assert_eq!(package.get_nevra(), "A-1:2.1.3-22.oe2203.x86_64");
+
+    let package = repo.get_package_by_id(1).unwrap();
+    assert_eq!(package.get_arch(), "noarch");
+    assert_eq!(package.get_license(), Some("GPLv2+"));
+    assert!(package.get_description().unwrap_or_default().is_empty());
+    assert!(package.get_vendor().unwrap_or_default().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_file_requires() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/file-requires.xml"
+    ))?;
+    let mut repo = repo::Repo::from_str(&xml)?;
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+    repo.set_filelists_loader(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/file-requires-filelists.xml"
+        ))?)
+    });
+    // Files listed in primary.xml do not need filelists.xml.
+    let sh = repo::RpmEntry {
+        name: "/bin/sh".to_string(),
+        flags: None,
+        epoch: None,
+        ver: None,
+        rel: None,
+    };
+    assert_eq!(repo.get_entry_provider_id(&sh), Some(&vec![1]));
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let mut ids = transaction.get_package_ids();
+            ids.sort();
+            assert_eq!(ids, vec![0, 1, 2]);
+        }
+        _ => panic!("A should be satisfied"),
+    }
+    assert!(matches!(
+        solve::get_transaction_in_repo(&repo, "D")?,
+        solve::SolveResult::MissingProviders(_)
+    ));
+    // filelists.xml is loaded only once.
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // A filelists.xml which can not be loaded fails the solving instead of the file requires.
+    let mut repo = repo::Repo::from_str(&xml)?;
+    repo.set_filelists_loader(|| Err(anyhow::anyhow!("filelists.xml not found")));
+    assert!(solve::get_transaction_in_repo(&repo, "C").is_ok());
+    assert!(solve::get_transaction_in_repo(&repo, "A").is_err());
+    let report = repoclosure::check_repo_closure(&repo, &solve::SolveOptions::default());
+    assert!(report.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_changelogs() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/package-record.xml"
+    ))?;
+    let load_other = || -> Result<String> {
+        Ok(fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/changelog-other.xml"
+        ))?)
+    };
+    let repo = repo::Repo::from_str(&xml)?;
+    assert!(repo.get_package_changelogs(0)?.is_empty());
+    let mut repo = repo::Repo::from_str(&xml)?;
+    repo.set_other_loader(load_other);
+    assert!(repo
+        .get_package_by_id(0)
+        .unwrap()
+        .get_changelogs()
+        .is_none());
+    let changelogs = repo.get_package_changelogs(0)?;
+    assert_eq!(changelogs.len(), 2);
+    assert_eq!(changelogs[0].author, "Alice <alice@example.org> - 2.1.3-21");
+    assert_eq!(changelogs[0].date, 1640995200);
+    assert_eq!(changelogs[0].text, "- Fix CVE-2021-0001");
+    assert_eq!(
+        changelogs[1].text,
+        "- Rebuild\n- Fix CVE-2022-0002 & CVE-2022-0003"
+    );
+    assert!(repo.get_package_changelogs(1)?.is_empty());
+    assert_eq!(
+        repo.get_package_by_id(0)
+            .unwrap()
+            .get_changelogs()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // The repos without other.xml, like @System, do not keep the others from being loaded.
+    let mut system = repo::Repo::from_str(&xml)?;
+    system.set_repo_id("@System");
+    let mut repo = repo::Repo::from_str(&xml)?;
+    repo.set_other_loader(load_other);
+    system.merge(repo);
+    assert!(system.get_package_changelogs(0)?.is_empty());
+    assert_eq!(system.get_package_changelogs(2)?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_parse_primary_xml_from_reader() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/package-record.xml"
+    ))?;
+    // Parse the packages while primary.xml.gz is decompressed.
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(xml.as_bytes())?;
+    let primary_gz = encoder.finish()?;
+    let repo = repo::Repo::from_reader(BufReader::new(GzDecoder::new(&primary_gz[..])))?;
+    assert_eq!(repo.get_package_count(), 2);
+    let package = repo.get_package_by_id(0).unwrap();
+    assert_eq!(package.get_nevra(), "A-1:2.1.3-22.oe2203.x86_64");
+    assert_eq!(package.get_license(), Some("MIT and BSD"));
+    assert_eq!(repo.get_package_requires_by_id(0).unwrap()[0].name, "B");
+    assert!(repo.get_package_requires_by_id(1).is_none());
+    assert_eq!(repo.get_package_ids_by_name("B"), vec![1]);
+
+    assert!(repo::Repo::from_reader(&b"<metadata><package><name>A</arch>"[..]).is_err());
+    Ok(())
+}
+
+// A directory in the temp dir, which is removed when the test ends, even if it fails.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Result<TempDir> {
+        let path = std::env::temp_dir().join(format!("rust-solv-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&path)?;
+        Ok(TempDir(path))
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Write repodata/repomd.xml and repodata/abc-primary.xml.gz of a repo into the directory,
+// and return the compressed primary.xml.
+fn write_repodata(dir: &Path, primary_xml: &str) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(primary_xml.as_bytes())?;
+    let primary_gz = encoder.finish()?;
+    let repodata = dir.join("repodata");
+    fs::create_dir_all(&repodata)?;
+    fs::write(repodata.join("abc-primary.xml.gz"), &primary_gz)?;
+    let repomd_xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<repomd xmlns="http://linux.duke.edu/metadata/repo">
+  <revision>1650000000</revision>
+  <data type="primary">
+    <checksum type="sha256">{:x}</checksum>
+    <open-checksum type="sha256">{:x}</open-checksum>
+    <location href="repodata/abc-primary.xml.gz"/>
+    <timestamp>1650000000</timestamp>
+  </data>
+</repomd>"#,
+        Sha256::digest(&primary_gz),
+        Sha256::digest(primary_xml.as_bytes())
+    );
+    fs::write(repodata.join("repomd.xml"), repomd_xml)?;
+    Ok(primary_gz)
+}
+
+#[test]
+fn test_offline_cache() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let cachedir = TempDir::new("offline")?;
+    let mut cache = cache::MetadataCache::new(&cachedir, "OS");
+    cache.set_offline(true);
+    // Nothing is cached yet, and nothing can be downloaded.
+    assert!(
+        repo::Repo::from_baseurl_with_cache("http://repo.invalid/OS", Some(cache.clone())).is_err()
+    );
+
+    let primary_gz = write_repodata(cache.get_dir(), &xml)?;
+    let repo = repo::Repo::from_baseurl_with_cache("http://repo.invalid/OS", Some(cache.clone()))?;
+    assert_eq!(repo.get_package_count(), 4);
+
+    // A corrupted cache can not be used, and can not be downloaded again in offline mode.
+    let repodata = cache.get_dir().join("repodata");
+    fs::write(repodata.join("abc-primary.xml.gz"), &primary_gz[1..])?;
+    assert!(repo::Repo::from_baseurl_with_cache("http://repo.invalid/OS", Some(cache)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_local_repo() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let dir = TempDir::new("local")?;
+    write_repodata(&dir, &xml)?;
+    let path = dir.to_str().unwrap();
+    for baseurl in [path.to_string(), format!("file://{}/", path)] {
+        let repo = repo::Repo::from_baseurl(&baseurl)?;
+        assert_eq!(repo.get_package_count(), 4);
+    }
+    assert!(repo::Repo::from_baseurl(&format!("file://{}/missing", path)).is_err());
+    // The file:// urls are percent-encoded.
+    write_repodata(&dir.join("my repo"), &xml)?;
+    let repo = repo::Repo::from_baseurl(&format!("file://localhost{}/my%20repo/", path))?;
+    assert_eq!(repo.get_package_count(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_mirror_failover() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/satisfied.xml"))?;
+    let dir = TempDir::new("mirrors")?;
+    let good = dir.join("good");
+    write_repodata(&good, &xml)?;
+    let repomd_xml = fs::read(good.join("repodata/repomd.xml"))?;
+    // The first mirror is unreachable and the second one is out of sync with the metalink.
+    let stale = dir.join("stale");
+    write_repodata(&stale, &xml.replace("<name>A</name>", "<name>Z</name>"))?;
+    let mirror_urls = [
+        format!("file://{}/", dir.join("missing").display()),
+        format!("file://{}/", stale.display()),
+        format!("file://{}/", good.display()),
+    ];
+
+    fs::write(dir.join("mirrorlist"), mirror_urls.join("\n"))?;
+    let mirrors = mirror::Mirrors::from_mirrorlist(&dir.join("mirrorlist").display().to_string());
+    let repo = repo::Repo::from_mirrors_with_cache(mirrors, None)?;
+    // Without a metalink, the stale mirror is used.
+    assert!(repo.get_package_id_by_name("Z").is_some());
+
+    // Several baseurls of a repo are failed over the same way.
+    let mirrors = mirror::Mirrors::from_baseurls(&[mirror_urls[0].clone(), mirror_urls[2].clone()]);
+    let repo = repo::Repo::from_mirrors_with_cache(mirrors, None)?;
+    assert!(repo.get_package_id_by_name("A").is_some());
+
+    let write_metalink = |sha256: String| -> Result<String> {
+        let urls: String = mirror_urls
+            .iter()
+            .map(|url| format!("<url protocol=\"file\">{}repodata/repomd.xml</url>", url))
+            .collect();
+        let metalink = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<metalink version="3.0" xmlns="http://www.metalinker.org/">
+  <files>
+    <file name="repomd.xml">
+      <verification><hash type="sha256">{}</hash></verification>
+      <resources>{}</resources>
+    </file>
+  </files>
+</metalink>"#,
+            sha256, urls
+        );
+        let path = dir.join("metalink.xml");
+        fs::write(&path, metalink)?;
+        Ok(path.display().to_string())
+    };
+    let metalink = write_metalink(format!("{:x}", Sha256::digest(&repomd_xml)))?;
+    let repo =
+        repo::Repo::from_mirrors_with_cache(mirror::Mirrors::from_metalink(&metalink), None)?;
+    assert!(repo.get_package_id_by_name("A").is_some());
+    assert!(repo.get_package_id_by_name("Z").is_none());
+
+    let metalink = write_metalink("0".repeat(64))?;
+    assert!(
+        repo::Repo::from_mirrors_with_cache(mirror::Mirrors::from_metalink(&metalink), None)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_merge_repos() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/merge-os.xml"))?;
+    let mut os = repo::Repo::from_str(&xml)?;
+    os.set_repo_id("OS");
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/merge-update.xml"
+    ))?;
+    let mut update = repo::Repo::from_str(&xml)?;
+    update.set_repo_id("update");
+    update.set_priority(10);
+    // A can not be installed from OS alone. Besides the missing C, B is too old.
+    match solve::get_transaction_in_repo(&os, "A")? {
+        solve::SolveResult::Unsatisfied(problems) => {
+            let messages: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "nothing provides C needed by A-1.0-1.x86_64",
+                    "nothing provides B >= 2.0 needed by A-1.0-1.x86_64",
+                ]
+            );
+        }
+        _ => panic!("package A should be unsatisfied"),
+    }
+
+    let mut repo = repo::Repo::default();
+    repo.merge(os);
+    repo.merge(update);
+    assert_eq!(repo.get_package_count(), 4);
+    assert_eq!(repo.get_package_ids_by_name("B"), vec![1, 2]);
+    assert_eq!(repo.get_package_repo_id(1), Some("OS"));
+    assert_eq!(repo.get_package_repo_id(2), Some("update"));
+    assert_eq!(repo.get_package_priority(0), repo::DEFAULT_PRIORITY);
+    assert_eq!(repo.get_package_priority(3), 10);
+    match solve::get_transaction_in_repo(&repo, "A")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let items: Vec<(&str, &str)> = transaction
+                .get_items()
+                .iter()
+                .map(|item| (item.nevra.as_str(), item.repo_id.as_str()))
+                .collect();
+            assert_eq!(
+                items,
+                vec![
+                    ("A-1.0-1.x86_64", "OS"),
+                    ("B-2.0-1.x86_64", "update"),
+                    ("C-1.0-1.noarch", "update"),
+                ]
+            );
+        }
+        _ => panic!("A should be satisfied"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_filter_packages() -> Result<()> {
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/merge-update.xml"
+    ))?;
+    let mut update = repo::Repo::from_str(&xml)?;
+    update.filter_packages(&["B-2.0*".to_string()], &[]);
+    assert_eq!(update.get_package_count(), 1);
+    assert_eq!(update.get_package_ids_by_name("C"), vec![0]);
+    assert!(update
+        .get_entry_provider_id(&repo::RpmEntry {
+            name: "B".to_string(),
+            flags: None,
+            epoch: None,
+            ver: None,
+            rel: None,
+        })
+        .is_none());
+
+    let mut update = repo::Repo::from_str(&xml)?;
+    update.filter_packages(&[], &["C.noarch".to_string()]);
+    assert_eq!(update.get_package_count(), 1);
+
+    // B-2.0 is excluded from update, so A can not be installed.
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/merge-os.xml"))?;
+    let mut repo = repo::Repo::from_str(&xml)?;
+    let mut update = repo::Repo::from_str(&fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/merge-update.xml"
+    ))?)?;
+    update.filter_packages(&["B".to_string()], &[]);
+    repo.merge(update);
+    assert!(matches!(
+        solve::get_transaction_in_repo(&repo, "A")?,
+        solve::SolveResult::Unsatisfied(_) | solve::SolveResult::MissingProviders(_)
+    ));
+    Ok(())
+}
+
+// An entry of an rpm header: the tag, the type and the data.
+type HeaderTag = (u32, u32, Vec<u8>);
+
+fn string_tag(tag: u32, string: &str) -> HeaderTag {
+    (tag, 6, [string.as_bytes(), b"\0"].concat())
+}
+
+fn string_array_tag(tag: u32, strings: &[&str]) -> HeaderTag {
+    let data = strings
+        .iter()
+        .flat_map(|s| [s.as_bytes(), b"\0"].concat())
+        .collect();
+    (tag, 8, data)
+}
+
+fn int32_array_tag(tag: u32, integers: &[u32]) -> HeaderTag {
+    (
+        tag,
+        4,
+        integers.iter().flat_map(|n| n.to_be_bytes()).collect(),
+    )
+}
+
+// Encode the tags into an rpm header blob, as it is stored in the rpmdb.
+fn encode_header(tags: &[HeaderTag]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut store: Vec<u8> = Vec::new();
+    for (tag, r#type, data) in tags {
+        // Integers are aligned in the data store.
+        if *r#type == 4 {
+            store.resize(store.len().div_ceil(4) * 4, 0);
+        }
+        let count = match r#type {
+            4 => data.len() / 4,
+            _ => data.iter().filter(|&&b| b == 0).count(),
+        };
+        for n in [*tag, *r#type, store.len() as u32, count as u32] {
+            index.extend_from_slice(&n.to_be_bytes());
+        }
+        store.extend_from_slice(data);
+    }
+    let mut blob = Vec::new();
+    blob.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    blob.extend_from_slice(&(store.len() as u32).to_be_bytes());
+    blob.extend_from_slice(&index);
+    blob.extend_from_slice(&store);
+    blob
+}
+
+fn encode_package_header(name: &str, version: &str, requires: &[(&str, u32, &str)]) -> Vec<u8> {
+    let evr = format!("{}-1", version);
+    let mut tags = vec![
+        string_tag(1000, name),
+        string_tag(1001, version),
+        string_tag(1002, "1"),
+        string_tag(1022, "x86_64"),
+        string_array_tag(1047, &[name]),
+        int32_array_tag(1112, &[8]),
+        string_array_tag(1113, &[&evr]),
+    ];
+    if !requires.is_empty() {
+        let names: Vec<&str> = requires.iter().map(|r| r.0).collect();
+        let flags: Vec<u32> = requires.iter().map(|r| r.1).collect();
+        let versions: Vec<&str> = requires.iter().map(|r| r.2).collect();
+        tags.push(int32_array_tag(1048, &flags));
+        tags.push(string_array_tag(1049, &names));
+        tags.push(string_array_tag(1050, &versions));
+    }
+    if name == "bar" {
+        tags.push(int32_array_tag(1116, &[0]));
+        tags.push(string_array_tag(1117, &["bar"]));
+        tags.push(string_array_tag(1118, &["/usr/bin/"]));
+    }
+    encode_header(&tags)
+}
+
+// The NEVRAs of the packages in the transaction, or None if the jobs can not be satisfied.
+fn get_nevras(result: solve::SolveResult) -> Option<Vec<String>> {
+    match result {
+        solve::SolveResult::Satisfied(transaction) => Some(
+            transaction
+                .get_items()
+                .iter()
+                .map(|item| item.nevra.clone())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// Write the rpmdb with the installed libfoo-1.0 and bar-1.0, which requires libfoo < 2.0,
+// under the installroot, and read it.
+fn read_installed(installroot: &Path) -> Result<repo::Repo> {
+    fs::create_dir_all(installroot.join("var/lib/rpm"))?;
+    let connection = rusqlite::Connection::open(installroot.join("var/lib/rpm/rpmdb.sqlite"))?;
+    connection.execute(
+        "CREATE TABLE Packages (hnum INTEGER PRIMARY KEY AUTOINCREMENT, blob BLOB NOT NULL)",
+        [],
+    )?;
+    // The rpmlib() dependency is dropped.
+    for blob in [
+        encode_package_header(
+            "libfoo",
+            "1.0",
+            &[("rpmlib(PayloadIsZstd)", 1 << 24, "5.4.18-1")],
+        ),
+        encode_package_header("bar", "1.0", &[("libfoo", 2, "2.0")]),
+    ] {
+        connection.execute("INSERT INTO Packages (blob) VALUES (?1)", [blob])?;
+    }
+    drop(connection);
+    repo::Repo::from_rpmdb(installroot)
+}
+
+#[test]
+fn test_installed_packages() -> Result<()> {
+    let installroot = TempDir::new("installroot")?;
+    let installed = read_installed(&installroot)?;
+    assert_eq!(installed.get_package_count(), 2);
+    assert_eq!(
+        installed.get_package_by_id(0).unwrap().get_nevra(),
+        "libfoo-1.0-1.x86_64"
+    );
+    assert!(installed.get_package_requires_by_id(0).is_none());
+    assert_eq!(
+        installed.get_package_requires_by_id(1).unwrap()[0].to_string(),
+        "libfoo < 2.0"
+    );
+
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/installed.xml"))?;
+    let mut repo = installed;
+    repo.merge(repo::Repo::from_str(&xml)?);
+    assert!(repo.is_package_installed(1));
+    assert!(!repo.is_package_installed(2));
+    let get_items =
+        |package_name| solve::get_transaction_in_repo(&repo, package_name).map(get_nevras);
+    // The installed libfoo and the file of the installed bar are used.
+    assert_eq!(get_items("A")?, Some(vec!["A-1.0-1.x86_64".to_string()]));
+    assert_eq!(get_items("C")?, Some(vec!["C-1.0-1.noarch".to_string()]));
+    // libfoo is upgraded to the highest version bar still accepts.
+    match solve::get_transaction_in_repo(&repo, "D")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let items = transaction.get_items();
+            assert_eq!(items.len(), 2);
+            assert_eq!(items[0].nevra, "libfoo-1.5-1.x86_64");
+            assert!(matches!(items[0].reason, solve::Reason::Upgrade(0)));
+            assert_eq!(items[1].nevra, "D-1.0-1.x86_64");
+        }
+        _ => panic!("D should be satisfied"),
+    }
+    // libfoo can not be upgraded to 2.0, which is required by B.
+    assert_eq!(get_items("B")?, None);
+    Ok(())
+}
+
+#[test]
+fn test_installed_requires() -> Result<()> {
+    let installroot = TempDir::new("installed-requires")?;
+    let mut repo = read_installed(&installroot)?;
+    let xml = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/installed-upgrade.xml"
+    ))?;
+    repo.merge(repo::Repo::from_str(&xml)?);
+    // libfoo is upgraded to 2.0 for B, so the installed bar needs libfoo-compat instead.
+    match solve::get_transaction_in_repo(&repo, "B")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let items = transaction.get_items();
+            let nevras: Vec<&str> = items.iter().map(|item| item.nevra.as_str()).collect();
+            assert_eq!(
+                nevras,
+                vec![
+                    "libfoo-2.0-1.x86_64",
+                    "B-1.0-1.x86_64",
+                    "libfoo-compat-1.0-1.x86_64"
+                ]
+            );
+            assert!(matches!(items[2].reason, solve::Reason::Requires(1, _)));
+        }
+        _ => panic!("B should be satisfied"),
+    }
+    // bar-ng replaces the installed bar, which has no upgrades.
+    match solve::get_transaction_in_repo(&repo, "bar-ng")? {
+        solve::SolveResult::Satisfied(transaction) => {
+            let items = transaction.get_items();
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].nevra, "bar-ng-1.0-1.x86_64");
+            assert!(matches!(items[0].reason, solve::Reason::Obsoletes(1)));
+        }
+        _ => panic!("bar-ng should be satisfied"),
+    }
+    Ok(())
+}
+
+// Write an .rpm file with the lead, a signature header, the main header and a fake payload.
+fn write_rpm_file(path: &Path, header_blob: &[u8]) -> Result<()> {
+    let mut rpm = vec![0xed, 0xab, 0xee, 0xdb, 3, 0, 0, 0];
+    rpm.resize(96, 0);
+    // The signature header of 36 bytes is padded to 40 bytes.
+    let signature_blob = encode_header(&[string_tag(1000, "abc")]);
+    for (blob, padding) in [(signature_blob, 4), (header_blob.to_vec(), 0)] {
+        rpm.extend_from_slice(&[0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0]);
+        rpm.extend_from_slice(&blob);
+        rpm.resize(rpm.len() + padding, 0);
+    }
+    rpm.extend_from_slice(b"payload");
+    fs::write(path, rpm)?;
+    Ok(())
+}
+
+#[test]
+fn test_rpm_files() -> Result<()> {
+    let dir = TempDir::new("rpms")?;
+    let path = dir.join("E-1.0-1.x86_64.rpm");
+    let header_blob = encode_package_header("E", "1.0", &[("libfoo", 12, "1.5")]);
+    write_rpm_file(&path, &header_blob)?;
+    let rpm = fs::read(&path)?;
+    let commandline = repo::Repo::from_rpm_files(&[&path])?;
+    assert_eq!(commandline.get_package_count(), 1);
+    let package = commandline.get_package_by_id(0).unwrap();
+    assert_eq!(package.get_nevra(), "E-1.0-1.x86_64");
+    assert_eq!(
+        package.get_checksum().unwrap().value,
+        format!("{:x}", Sha256::digest(&rpm))
+    );
+    assert_eq!(package.get_location_href(), path.to_str());
+    let header_range = package.get_header_range().unwrap();
+    assert_eq!(header_range.start, 136);
+    assert_eq!(header_range.end, 136 + 8 + header_blob.len() as u64);
+    assert_eq!(
+        commandline.get_package_repo_id(0),
+        Some(repo::COMMANDLINE_REPO_ID)
+    );
+
+    // The local package is combined with a repo providing its dependencies.
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/installed.xml"))?;
+    let mut repo = repo::Repo::from_str(&xml)?;
+    let package_id = repo.get_package_count();
+    repo.merge(commandline);
+    let jobs = [solve::Job::InstallPackage(package_id)];
+    let result = solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())?;
+    assert_eq!(
+        get_nevras(result).expect("E should be satisfied"),
+        vec!["E-1.0-1.x86_64", "libfoo-2.0-1.x86_64"]
+    );
+
+    assert!(repo::Repo::from_rpm_files(&[concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/installed.xml"
+    )])
+    .is_err());
+    // A header declaring a data store of 512 MiB is rejected before it is read.
+    let path = dir.join("huge.rpm");
+    write_rpm_file(&path, &[0, 0, 0, 1, 0x20, 0, 0, 0])?;
+    assert!(repo::Repo::from_rpm_files(&[&path]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_weak_deps() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/weak-deps.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    assert_eq!(repo.get_package_recommends_by_id(0).unwrap().len(), 3);
+    assert_eq!(repo.get_package_suggests_by_id(0).unwrap()[0].name, "D");
+    assert_eq!(repo.get_package_enhances_by_id(3).unwrap()[0].name, "A");
+    assert_eq!(repo.get_package_supplements_by_id(5).unwrap()[0].name, "A");
+
+    let get_items = |options: &solve::SolveOptions| -> Result<Vec<String>> {
+        let jobs = [solve::Job::Install("A".to_string())];
+        let result = solve::solve_jobs_in_repo(&repo, &jobs, options)?;
+        Ok(get_nevras(result).expect("A should be satisfied"))
+    };
+    // C and its requirement E are recommended, and F supplements A. Nothing provides one of
+    // the recommends, and X conflicts with B, but they do not make A uninstallable.
+    // D is only suggested, so it is not installed.
+    assert_eq!(
+        get_items(&solve::SolveOptions::default())?,
+        vec![
+            "A-1.0-1.x86_64",
+            "B-1.0-1.x86_64",
+            "C-1.0-1.x86_64",
+            "F-1.0-1.x86_64",
+            "E-1.0-1.x86_64"
+        ]
+    );
+    let options = solve::SolveOptions {
+        install_weak_deps: false,
+        ..solve::SolveOptions::default()
+    };
+    assert_eq!(
+        get_items(&options)?,
+        vec!["A-1.0-1.x86_64", "B-1.0-1.x86_64"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_rich_deps() -> Result<()> {
+    let xml = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/rich-deps.xml"))?;
+    let repo = repo::Repo::from_str(&xml)?;
+    assert_eq!(
+        repo.get_package_requires_by_id(0).unwrap()[1].name,
+        "(libA >= 1.0 with libA < 2.0)"
+    );
+
+    let solve = |names: &[&str]| -> Result<solve::SolveResult> {
+        let jobs: Vec<solve::Job> = names
+            .iter()
+            .map(|name| solve::Job::Install(name.to_string()))
+            .collect();
+        solve::solve_jobs_in_repo(&repo, &jobs, &solve::SolveOptions::default())
+    };
+    // python3-foo is required once python3 is pulled in by tool, the only libA between 1.0
+    // and 2.0 is chosen, and langpack-en is recommended, which makes langpack-en-extras
+    // supplement App as well. minimal is never installed for the unless, so minimal-docs
+    // does not supplement App.
+    assert_eq!(
+        get_nevras(solve(&["App"])?).expect("App should be satisfied"),
+        vec![
+            "App-1.0-1.x86_64",
+            "tool-1.0-1.x86_64",
+            "libA-1.5-1.x86_64",
+            "gui-Y-1.0-1.x86_64",
+            "python3-3.9-1.x86_64",
+            "python3-foo-1.0-1.x86_64",
+            "langpack-en-1.0-1.x86_64",
+            "langpack-en-extras-1.0-1.x86_64"
+        ]
+    );
+    match solve(&["Broken"])? {
+        solve::SolveResult::MissingProviders(problems) => assert_eq!(
+            problems[0].to_string(),
+            "nothing provides (missing-a or (missing-b and libA)) needed by Broken-1.0-1.x86_64"
+        ),
+        _ => panic!("Broken should miss providers"),
+    }
+    match solve(&["App", "Conflicter"])? {
+        solve::SolveResult::Unsatisfied(problems) => assert!(problems.iter().any(|problem| {
+            problem.to_string()
+                == "package Conflicter-1.0-1.x86_64 conflicts with \
+                    (libA >= 1.0 with libA < 2.0) provided by libA-1.5-1.x86_64"
+        })),
+        _ => panic!("App and Conflicter should conflict"),
+    }
+    // The if and unless of conflicts are conditions on the conflicting packages, so the
+    // conflicts never require anything, even if nothing provides their conditions.
+    assert_eq!(
+        get_nevras(solve(&["IfConflicter"])?).expect("IfConflicter should be satisfied"),
+        vec!["IfConflicter-1.0-1.x86_64"]
+    );
+    assert!(matches!(
+        solve(&["App", "IfConflicter"])?,
+        solve::SolveResult::Unsatisfied(_)
+    ));
+    assert_eq!(
+        get_nevras(solve(&["UnlessConflicter"])?).expect("UnlessConflicter should be satisfied"),
+        vec!["UnlessConflicter-1.0-1.x86_64"]
+    );
+    // python3 is installed together with python3-foo.
+    assert!(get_nevras(solve(&["App", "UnlessConflicter"])?).is_some());
+    Ok(())
+}